@@ -1,12 +1,16 @@
 use crate::err_handle::{ChimeraCompileError, ChimeraRuntimeFailure, VarTypes};
+use crate::frontend;
 use crate::frontend::{Context, Rule};
 use crate::literal::{Data, DataKind, Literal, NumberKind};
-use crate::{frontend, CLIENT};
+use crate::util::config::RetryPolicy;
+use crate::util::transport::TransportError;
 use pest::iterators::Pair;
+use regex::Regex;
 use reqwest::header::{HeaderMap, HeaderName};
 use std::collections::HashMap;
 use std::fmt::Formatter;
 use std::ops::Deref;
+use std::time::Duration;
 
 // This has a return value despite only panicking so satisfy the compiler, as it's called inside of
 // `ok_or_else(|| no_pairs_panic())` closures which are meant to transform an Option into a Result.
@@ -22,6 +26,7 @@ fn no_pairs_panic(rule_name: &str) -> ChimeraCompileError {
 #[derive(Debug)]
 pub struct ChimeraScriptAST {
     pub functions: Vec<Function>,
+    pub params: Vec<ParamsBlock>,
 }
 
 impl ChimeraScriptAST {
@@ -33,15 +38,194 @@ impl ChimeraScriptAST {
             panic!("Expected the first pair of a parse to be Rule::Main but it was not")
         };
         let function_pairs = main_pair.into_inner();
-        let mut functions: Vec<Function> = Vec::new();
+        let mut functions: Vec<(Function, (usize, usize))> = Vec::new();
+        let mut params: Vec<ParamsBlock> = Vec::new();
         for function_pair in function_pairs {
-            if function_pair.as_rule() == Rule::EOI {
-                break;
+            match function_pair.as_rule() {
+                Rule::EOI => break,
+                Rule::ParamsBlock => {
+                    let line_col = function_pair.line_col();
+                    let params_block = Self::parse_rule_to_params_block(function_pair)?;
+                    if params.iter().any(|other| other.name == params_block.name) {
+                        return Err(ChimeraCompileError::new(
+                            format!("PARAMS {} is defined more than once", params_block.name)
+                                .as_str(),
+                            line_col,
+                        ));
+                    }
+                    params.push(params_block);
+                }
+                _ => {
+                    let line_col = function_pair.line_col();
+                    functions.push((Self::pair_to_function(function_pair)?, line_col));
+                }
+            }
+        }
+        for (function, line_col) in &functions {
+            match function.get_value("params") {
+                Some(params_name) if !params.iter().any(|params| params.name == params_name) => {
+                    return Err(ChimeraCompileError::new(
+                        format!("PARAMS {} is not defined in this file", params_name).as_str(),
+                        *line_col,
+                    ))
+                }
+                _ => (),
             }
-            let function = Self::pair_to_function(function_pair)?;
-            functions.push(function);
         }
-        Ok(Self { functions })
+        let functions = Self::order_by_dependencies(functions)?;
+        Ok(Self { functions, params })
+    }
+
+    /// Order the functions of a file so every test runs after the tests it depends on, keeping the
+    /// order of the file otherwise. A dependency on something which is not a test in the file, or a
+    /// cycle of dependencies, fails to compile
+    fn order_by_dependencies(
+        functions: Vec<(Function, (usize, usize))>,
+    ) -> Result<Vec<Function>, ChimeraCompileError> {
+        let mut dependencies: Vec<Vec<usize>> = Vec::new();
+        for (function, line_col) in &functions {
+            let mut function_dependencies: Vec<usize> = Vec::new();
+            for dependency in function.dependencies() {
+                let index = functions.iter().position(|(other, _)| {
+                    other.is_test_function() && other.hook().is_none() && other.has_name(dependency)
+                });
+                match index {
+                    Some(index) => function_dependencies.push(index),
+                    None => {
+                        return Err(ChimeraCompileError::new(
+                            format!(
+                                "Case {} depends on {}, which is not a test in this file",
+                                function.name, dependency
+                            )
+                            .as_str(),
+                            *line_col,
+                        ))
+                    }
+                }
+            }
+            dependencies.push(function_dependencies);
+        }
+
+        let mut visited = vec![false; functions.len()];
+        let mut path: Vec<usize> = Vec::new();
+        let mut order: Vec<usize> = Vec::new();
+        for index in 0..functions.len() {
+            Self::visit_dependencies(
+                index,
+                &functions,
+                &dependencies,
+                &mut visited,
+                &mut path,
+                &mut order,
+            )?;
+        }
+        let mut functions: Vec<Option<Function>> = functions
+            .into_iter()
+            .map(|(function, _)| Some(function))
+            .collect();
+        Ok(order
+            .into_iter()
+            .map(|index| {
+                functions[index]
+                    .take()
+                    .expect("A function was ordered more than once")
+            })
+            .collect())
+    }
+
+    /// Depth first search adding a function to `order` after everything it depends on. `path` holds the
+    /// functions being visited, so reaching one of them again means the dependencies form a cycle
+    fn visit_dependencies(
+        index: usize,
+        functions: &[(Function, (usize, usize))],
+        dependencies: &[Vec<usize>],
+        visited: &mut [bool],
+        path: &mut Vec<usize>,
+        order: &mut Vec<usize>,
+    ) -> Result<(), ChimeraCompileError> {
+        if visited[index] {
+            return Ok(());
+        }
+        if let Some(cycle_start) = path.iter().position(|&on_path| on_path == index) {
+            let cycle: Vec<&str> = path[cycle_start..]
+                .iter()
+                .chain(std::iter::once(&index))
+                .map(|&in_cycle| functions[in_cycle].0.name.as_str())
+                .collect();
+            return Err(ChimeraCompileError::new(
+                format!(
+                    "Cases depend on each other in a cycle: {}",
+                    cycle.join(" -> ")
+                )
+                .as_str(),
+                functions[index].1,
+            ));
+        }
+        path.push(index);
+        for &dependency in &dependencies[index] {
+            Self::visit_dependencies(dependency, functions, dependencies, visited, path, order)?;
+        }
+        path.pop();
+        visited[index] = true;
+        order.push(index);
+        Ok(())
+    }
+
+    fn parse_rule_to_params_block(pair: Pair<Rule>) -> Result<ParamsBlock, ChimeraCompileError> {
+        // A ParamsBlock is going to contain
+        // 1. The name test functions use to refer to it
+        // 2. Either a table of column names and rows of literals, or a file to load the rows from
+        let line = pair.line_col().0 as i32;
+        let mut pairs = pair.into_inner();
+        let name = pairs
+            .next()
+            .ok_or_else(|| no_pairs_panic("ParamsBlock name"))?
+            .as_str()
+            .to_owned();
+        let source_pair = pairs
+            .next()
+            .ok_or_else(|| no_pairs_panic("ParamsBlock source"))?;
+        let source = match source_pair.as_rule() {
+            Rule::ParamsFile => {
+                let path_pair = source_pair
+                    .into_inner()
+                    .next()
+                    .ok_or_else(|| no_pairs_panic("ParamsFile path"))?;
+                ParamsSource::File(Self::parse_quotestring_rule(path_pair)?)
+            }
+            _ => {
+                let mut table_pairs = source_pair.into_inner();
+                let columns: Vec<String> = table_pairs
+                    .next()
+                    .ok_or_else(|| no_pairs_panic("ParamsTable columns"))?
+                    .into_inner()
+                    .map(|column| column.as_str().to_owned())
+                    .collect();
+                let mut rows: Vec<Vec<Literal>> = Vec::new();
+                for row_pair in table_pairs {
+                    let line_col = row_pair.line_col();
+                    let row = row_pair
+                        .into_inner()
+                        .map(Self::parse_rule_to_literal_value)
+                        .collect::<Result<Vec<Literal>, ChimeraCompileError>>()?;
+                    if row.len() != columns.len() {
+                        return Err(ChimeraCompileError::new(
+                            format!(
+                                "PARAMS {} has {} columns but a row has {} values",
+                                name,
+                                columns.len(),
+                                row.len()
+                            )
+                            .as_str(),
+                            line_col,
+                        ));
+                    }
+                    rows.push(row);
+                }
+                ParamsSource::Table { columns, rows }
+            }
+        };
+        Ok(ParamsBlock { name, source, line })
     }
 
     fn pair_to_function(function_pair: Pair<Rule>) -> Result<Function, ChimeraCompileError> {
@@ -53,6 +237,8 @@ impl ChimeraScriptAST {
             .next()
             .expect("Rule::Function contained no inner pairs when it must have at least two");
         let mut decorators: Vec<Decorator> = Vec::new();
+        let mut timeout: Option<Duration> = None;
+        let mut retries: u32 = 0;
         if current_pair.as_rule() == Rule::Decorators {
             let decorator_pairs = current_pair.into_inner();
             for decorator_pair in decorator_pairs {
@@ -61,6 +247,7 @@ impl ChimeraScriptAST {
                         decorators.push(Decorator::Key(decorator_pair.as_str().to_owned()))
                     }
                     Rule::DecoratorKeyValuePair => {
+                        let line_col = decorator_pair.line_col();
                         let mut kv_inner = decorator_pair.into_inner();
                         let key_pair = kv_inner
                             .next()
@@ -68,10 +255,24 @@ impl ChimeraScriptAST {
                         let value_pair = kv_inner
                             .next()
                             .expect("A Rule::DecoratorKeyValuePair must contain a value pair");
-                        decorators.push(Decorator::KeyValue((
-                            key_pair.as_str().to_owned(),
-                            value_pair.as_str().to_owned(),
-                        )));
+                        // A quoted value is taken without its quotes
+                        let value = match value_pair.as_rule() {
+                            Rule::DecoratorString => value_pair
+                                .into_inner()
+                                .next()
+                                .expect("A Rule::DecoratorString must contain its inner string")
+                                .as_str()
+                                .to_owned(),
+                            _ => value_pair.as_str().to_owned(),
+                        };
+                        match key_pair.as_str() {
+                            "timeout" => {
+                                timeout = Some(Self::parse_timeout_decorator(&value, line_col)?)
+                            }
+                            "retry" => retries = Self::parse_retry_decorator(&value, line_col)?,
+                            _ => (),
+                        }
+                        decorators.push(Decorator::KeyValue((key_pair.as_str().to_owned(), value)));
                     }
                     _ => panic!("Got an invalid Rule variant inside of a Rule::Decorator"),
                 }
@@ -91,9 +292,49 @@ impl ChimeraScriptAST {
             decorators,
             name,
             block,
+            timeout,
+            retries,
+        })
+    }
+
+    /// Parse the value of a `[retry=N]` decorator, a whole number of times to retry which is not negative
+    fn parse_retry_decorator(
+        value: &str,
+        line_col: (usize, usize),
+    ) -> Result<u32, ChimeraCompileError> {
+        value.parse::<u32>().map_err(|_| {
+            ChimeraCompileError::new(
+                format!(
+                    "[retry={}] is not a valid retry count, expected a whole number",
+                    value
+                )
+                .as_str(),
+                line_col,
+            )
         })
     }
 
+    /// Parse the value of a `[timeout=N]` decorator, a number of seconds which is not negative
+    fn parse_timeout_decorator(
+        value: &str,
+        line_col: (usize, usize),
+    ) -> Result<Duration, ChimeraCompileError> {
+        value
+            .parse::<f64>()
+            .ok()
+            .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+            .ok_or_else(|| {
+                ChimeraCompileError::new(
+                    format!(
+                        "[timeout={}] is not a valid timeout, expected a number of seconds",
+                        value
+                    )
+                    .as_str(),
+                    line_col,
+                )
+            })
+    }
+
     fn pair_to_block(block_pair: Pair<Rule>) -> Result<Vec<BlockContents>, ChimeraCompileError> {
         if block_pair.as_rule() != Rule::Block {
             panic!("Expected rule to be Rule::Block when parsing into a Vec<BlockContents>")
@@ -106,7 +347,16 @@ impl ChimeraScriptAST {
                     BlockContents::Statement(ChimeraScriptAST::pair_to_statement(block_content)?)
                 }
                 Rule::Function => {
-                    BlockContents::Function(ChimeraScriptAST::pair_to_function(block_content)?)
+                    let line_col = block_content.line_col();
+                    let nested_function = ChimeraScriptAST::pair_to_function(block_content)?;
+                    // Nested cases always run in order, after the case they are nested in
+                    if !nested_function.dependencies().is_empty() {
+                        return Err(ChimeraCompileError::new(
+                            "Only cases which are not nested can depend on other cases",
+                            line_col,
+                        ));
+                    }
+                    BlockContents::Function(nested_function)
                 }
                 Rule::Teardown => {
                     BlockContents::Teardown(ChimeraScriptAST::pair_to_teardown(block_content)?)
@@ -185,10 +435,40 @@ impl ChimeraScriptAST {
                 let value = ChimeraScriptAST::parse_rule_to_value(value_pair)?;
                 Ok(Statement::PrintCommand(value))
             }
+            Rule::SleepCommand => {
+                // A SleepCommand is going to contain
+                // 1. A value for the number of seconds to sleep
+                let mut pairs = statement_inner.into_inner();
+                let value_pair = pairs
+                    .next()
+                    .ok_or_else(|| no_pairs_panic("SleepCommand's value"))?;
+                let value = ChimeraScriptAST::parse_rule_to_value(value_pair)?;
+                Ok(Statement::SleepCommand(value))
+            }
+            Rule::SkipCommand | Rule::FailCommand => {
+                // A SkipCommand or FailCommand is going to contain
+                // 1. A value for the reason the test was ended
+                let is_skip = statement_inner.as_rule() == Rule::SkipCommand;
+                let mut pairs = statement_inner.into_inner();
+                let value_pair = pairs
+                    .next()
+                    .ok_or_else(|| no_pairs_panic("SkipCommand or FailCommand's reason"))?;
+                let value = ChimeraScriptAST::parse_rule_to_value(value_pair)?;
+                match is_skip {
+                    true => Ok(Statement::SkipCommand(value)),
+                    false => Ok(Statement::FailCommand(value)),
+                }
+            }
             Rule::Expression => {
                 let expression = ChimeraScriptAST::parse_rule_to_expression(statement_inner)?;
                 Ok(Statement::Expression(expression))
             }
+            Rule::EventuallyBlock => Ok(Statement::Eventually(
+                Self::parse_rule_to_eventually_block(statement_inner)?,
+            )),
+            Rule::AssertErrorBlock => Ok(Statement::AssertError(
+                Self::parse_rule_to_assert_error_block(statement_inner)?,
+            )),
             _ => Err(ChimeraCompileError::new(
                 "Did not get a valid statement",
                 statement_inner.line_col(),
@@ -198,11 +478,14 @@ impl ChimeraScriptAST {
 
     fn parse_rule_to_assertion(pair: Pair<Rule>) -> Result<AssertCommand, ChimeraCompileError> {
         // An AssertCommand inner is going to contain
+        // 0. Optional Expect, in place of an ASSERT
         // 1. Optional Negation
         // 2. AssertSubCommand
-        // 3. Value
+        // 3. Optional Unordered modifier
         // 4. Value
-        // 5. Optional QuoteString
+        // 5. Value
+        // 6. A Tolerance if the subcommand is an APPROX
+        // 7. Optional QuoteString
         if pair.as_rule() != Rule::AssertCommand {
             return Err(ChimeraCompileError::new(
                 "Did not get a valid AssertCommand value",
@@ -211,6 +494,14 @@ impl ChimeraScriptAST {
         }
         let mut pairs = pair.into_inner();
 
+        let soft = match pairs.peek() {
+            Some(next) => next.as_rule() == Rule::Expect,
+            None => panic!("Expected a Rule::AssertCommand to contain inner pairs but it did not"),
+        };
+        if soft {
+            let _ = pairs.next();
+        }
+
         // Peek ahead to see if our inner contains an optional Negation
         let negate_assertion = match pairs.peek() {
             Some(next) => next.as_rule() == Rule::Negation,
@@ -227,13 +518,75 @@ impl ChimeraScriptAST {
         let subcommand_pair = pairs
             .next()
             .ok_or_else(|| no_pairs_panic("AssertCommand subcommand"))?;
-        if subcommand_pair.as_rule() != Rule::AssertSubCommand {
+        if subcommand_pair.as_rule() != Rule::AssertSubCommand
+            && subcommand_pair.as_rule() != Rule::MatchesSubCommand
+            && subcommand_pair.as_rule() != Rule::ApproxSubCommand
+            && subcommand_pair.as_rule() != Rule::SnapshotSubCommand
+            && subcommand_pair.as_rule() != Rule::StatusSubCommand
+            && subcommand_pair.as_rule() != Rule::HeaderSubCommand
+        {
             return Err(ChimeraCompileError::new(
                 "Got invalid data when reading an assertion subcommand",
                 subcommand_pair.line_col(),
             ));
         }
-        let subcommand = match subcommand_pair.as_span().as_str() {
+        // APPROX, SNAPSHOT, and HEADER subcommands hold options which come after the first value,
+        // so the subcommand is built once the values have been read
+        let subcommand_name = subcommand_pair.as_span().as_str();
+        let subcommand_line_col = subcommand_pair.line_col();
+
+        let unordered = match pairs.peek() {
+            Some(next) => next.as_rule() == Rule::Unordered,
+            None => false,
+        };
+        if unordered {
+            let unordered_pair = pairs.next().ok_or_else(|| no_pairs_panic("Unordered"))?;
+            if !matches!(subcommand_name, "EQUALS" | "CONTAINS" | "SUBSET") {
+                return Err(ChimeraCompileError::new(
+                    "UNORDERED can only be used with an EQUALS, CONTAINS, or SUBSET assertion",
+                    unordered_pair.line_col(),
+                ));
+            }
+        }
+
+        // Get the first value we're asserting with
+        let left_value_pair = pairs
+            .next()
+            .ok_or_else(|| no_pairs_panic("AssertCommand's first value param"))?;
+        let left_value = ChimeraScriptAST::parse_rule_to_value(left_value_pair)?;
+
+        // A HEADER names the header between the two values, which can be followed by a MATCHES
+        let header = match subcommand_name {
+            "HEADER" => {
+                let name_pair = pairs
+                    .next()
+                    .ok_or_else(|| no_pairs_panic("AssertCommand's header name"))?;
+                let name = ChimeraScriptAST::parse_rule_to_value(name_pair)?;
+                let matches = match pairs.peek() {
+                    Some(next) => next.as_rule() == Rule::MatchesSubCommand,
+                    None => false,
+                };
+                if matches {
+                    let _ = pairs.next();
+                }
+                Some((name, matches))
+            }
+            _ => None,
+        };
+
+        // Get the second value we're asserting with, which is a regex pattern for a MATCHES
+        let right_value_pair = pairs
+            .next()
+            .ok_or_else(|| no_pairs_panic("AssertCommand's second value param"))?;
+        let right_value = match right_value_pair.as_rule() {
+            Rule::Pattern => ChimeraScriptAST::parse_rule_to_pattern(right_value_pair)?,
+            Rule::StatusMatcher => {
+                ChimeraScriptAST::parse_rule_to_status_matcher(right_value_pair)?
+            }
+            _ => ChimeraScriptAST::parse_rule_to_value(right_value_pair)?,
+        };
+
+        let subcommand = match subcommand_name {
             "EQUALS" => AssertSubCommand::Equals,
             "GTE" => AssertSubCommand::GTE,
             "GT" => AssertSubCommand::GT,
@@ -242,26 +595,38 @@ impl ChimeraScriptAST {
             "STATUS" => AssertSubCommand::Status,
             "LENGTH" => AssertSubCommand::Length,
             "CONTAINS" => AssertSubCommand::Contains,
+            "MATCHES" => AssertSubCommand::Matches,
+            "SCHEMA" => AssertSubCommand::Schema,
+            "TYPE" => AssertSubCommand::Type,
+            "SUBSET" => AssertSubCommand::Subset,
+            "APPROX" => {
+                let tolerance_pair = pairs
+                    .next()
+                    .ok_or_else(|| no_pairs_panic("AssertCommand's tolerance"))?;
+                ChimeraScriptAST::parse_rule_to_approx(tolerance_pair)?
+            }
+            "SNAPSHOT" => {
+                let ignore = match pairs.peek() {
+                    Some(next) if next.as_rule() == Rule::SnapshotIgnore => {
+                        let _ = pairs.next();
+                        ChimeraScriptAST::parse_rule_to_snapshot_ignore(next)?
+                    }
+                    _ => Vec::new(),
+                };
+                AssertSubCommand::Snapshot { ignore }
+            }
+            "HEADER" => match header {
+                Some((name, matches)) => AssertSubCommand::Header { name, matches },
+                None => return Err(no_pairs_panic("AssertCommand's header name")),
+            },
             _ => {
                 return Err(ChimeraCompileError::new(
                     "Got an invalid assertion subcommand value",
-                    subcommand_pair.line_col(),
+                    subcommand_line_col,
                 ))
             }
         };
 
-        // Get the first value we're asserting with
-        let left_value_pair = pairs
-            .next()
-            .ok_or_else(|| no_pairs_panic("AssertCommand's first value param"))?;
-        let left_value = ChimeraScriptAST::parse_rule_to_value(left_value_pair)?;
-
-        // Get the second value we're asserting with
-        let right_value_pair = pairs
-            .next()
-            .ok_or_else(|| no_pairs_panic("AssertCommand's second value param"))?;
-        let right_value = ChimeraScriptAST::parse_rule_to_value(right_value_pair)?;
-
         // Check for an optional error message, can be a literal quotestring or a formatted string
         let error_message = match pairs.peek() {
             Some(next) => match next.as_rule() {
@@ -282,14 +647,88 @@ impl ChimeraScriptAST {
         };
 
         Ok(AssertCommand {
+            soft,
             negate_assertion,
             subcommand,
+            unordered,
             left_value,
             right_value,
             error_message,
         })
     }
 
+    fn parse_rule_to_approx(pair: Pair<Rule>) -> Result<AssertSubCommand, ChimeraCompileError> {
+        if pair.as_rule() != Rule::Tolerance {
+            return Err(ChimeraCompileError::new(
+                "Did not get a valid tolerance",
+                pair.line_col(),
+            ));
+        }
+        let mut tolerance_pairs = pair.into_inner();
+        let value_pair = tolerance_pairs
+            .next()
+            .ok_or_else(|| no_pairs_panic("Tolerance"))?;
+        let tolerance = ChimeraScriptAST::parse_rule_to_value(value_pair)?;
+        let relative = tolerance_pairs.next().is_some();
+        Ok(AssertSubCommand::Approx {
+            tolerance,
+            relative,
+        })
+    }
+
+    // A status matcher is a status code, a status class like 2xx, or a list of either
+    fn parse_rule_to_status_matcher(pair: Pair<Rule>) -> Result<Value, ChimeraCompileError> {
+        let inner = pair
+            .into_inner()
+            .next()
+            .ok_or_else(|| no_pairs_panic("StatusMatcher"))?;
+        match inner.as_rule() {
+            Rule::StatusList => {
+                let mut codes: Vec<Value> = Vec::new();
+                for code_pair in inner.into_inner() {
+                    let code = code_pair
+                        .into_inner()
+                        .next()
+                        .ok_or_else(|| no_pairs_panic("StatusCode"))?;
+                    codes.push(Self::parse_rule_to_status_code(code)?);
+                }
+                Ok(Value::List(codes))
+            }
+            _ => Self::parse_rule_to_status_code(inner),
+        }
+    }
+
+    fn parse_rule_to_status_code(pair: Pair<Rule>) -> Result<Value, ChimeraCompileError> {
+        match pair.as_rule() {
+            Rule::StatusClass => Ok(Value::Literal(Literal::String(
+                pair.as_str().to_ascii_lowercase(),
+            ))),
+            _ => Self::parse_rule_to_value(pair),
+        }
+    }
+
+    fn parse_rule_to_snapshot_ignore(pair: Pair<Rule>) -> Result<Vec<Value>, ChimeraCompileError> {
+        let inner = pair
+            .into_inner()
+            .next()
+            .ok_or_else(|| no_pairs_panic("SnapshotIgnore"))?;
+        if inner.as_rule() != Rule::IgnoreList {
+            return Ok(vec![ChimeraScriptAST::parse_rule_to_value(inner)?]);
+        }
+        let mut ignore: Vec<Value> = Vec::new();
+        for list_pair in inner.into_inner() {
+            let value_pair = match list_pair.as_rule() {
+                Rule::CommaSeparatedValues => list_pair
+                    .into_inner()
+                    .next()
+                    .ok_or_else(|| no_pairs_panic("CommaSeparatedValues"))?,
+                _ => list_pair,
+            };
+            ignore.push(ChimeraScriptAST::parse_rule_to_value(value_pair)?);
+        }
+        Ok(ignore)
+    }
+
     fn parse_rule_to_variable_name(pair: Pair<Rule>) -> Result<String, ChimeraCompileError> {
         if pair.as_rule() != Rule::VariableValue {
             return Err(ChimeraCompileError::new(
@@ -332,6 +771,31 @@ impl ChimeraScriptAST {
         }
     }
 
+    fn parse_rule_to_pattern(pair: Pair<Rule>) -> Result<Value, ChimeraCompileError> {
+        if pair.as_rule() != Rule::Pattern {
+            return Err(ChimeraCompileError::new(
+                "Did not get a valid regex pattern",
+                pair.line_col(),
+            ));
+        }
+        let inner = pair
+            .into_inner()
+            .next()
+            .ok_or_else(|| no_pairs_panic("Pattern"))?;
+        match inner.as_rule() {
+            Rule::RegexPattern => {
+                let pattern = inner
+                    .into_inner()
+                    .next()
+                    .expect("A Rule::RegexPattern must contain an inner value but it didn't")
+                    .as_str()
+                    .replace("\\\"", "\"");
+                Ok(Value::Literal(Literal::String(pattern)))
+            }
+            _ => ChimeraScriptAST::parse_rule_to_value(inner),
+        }
+    }
+
     fn parse_quotestring_rule(pair: Pair<Rule>) -> Result<String, ChimeraCompileError> {
         if pair.as_rule() != Rule::QuoteString {
             return Err(ChimeraCompileError::new(
@@ -523,6 +987,145 @@ impl ChimeraScriptAST {
         Ok(query_params)
     }
 
+    fn parse_rule_to_key_value_pair(pair: Pair<Rule>) -> Result<KeyValuePair, ChimeraCompileError> {
+        if pair.as_rule() != Rule::KeyValuePair {
+            return Err(ChimeraCompileError::new(
+                "Did not get a valid key value pair",
+                pair.line_col(),
+            ));
+        }
+        let mut key_value_pairs = pair.into_inner();
+
+        let assignment_token = key_value_pairs
+            .next()
+            .ok_or_else(|| no_pairs_panic("KeyValuePair"))?;
+        if assignment_token.as_rule() != Rule::VariableNameAssignment {
+            return Err(ChimeraCompileError::new(
+                "Did not get a valid key for a key value pair",
+                assignment_token.line_col(),
+            ));
+        }
+        let key = assignment_token.as_str().to_owned();
+
+        let value_token = key_value_pairs
+            .next()
+            .ok_or_else(|| no_pairs_panic("KeyValuePair"))?;
+        let value = ChimeraScriptAST::parse_rule_to_value(value_token)?;
+
+        Ok(KeyValuePair { key, value })
+    }
+
+    /// Check that a request option is one a request accepts, and that its value is valid when it is
+    /// written as a literal
+    fn check_request_option(
+        key_value: &KeyValuePair,
+        line_col: (usize, usize),
+    ) -> Result<(), ChimeraCompileError> {
+        if !REQUEST_OPTIONS.contains(&key_value.key.as_str()) {
+            return Err(ChimeraCompileError::new(
+                &format!(
+                    "'{}' is not a valid request option, expected one of {}",
+                    key_value.key,
+                    REQUEST_OPTIONS.join(", ")
+                ),
+                line_col,
+            ));
+        }
+        let Value::Literal(literal) = &key_value.value else {
+            return Ok(());
+        };
+        let is_valid = match (key_value.key.as_str(), literal) {
+            ("retries", Literal::Number(NumberKind::U64(_))) => true,
+            ("backoff" | "timeout", Literal::Number(seconds)) => {
+                seconds.to_f64().is_finite() && seconds.to_f64() >= 0.0
+            }
+            ("retry_on", Literal::String(kinds)) => parse_retry_on(kinds).is_some(),
+            _ => false,
+        };
+        match is_valid {
+            true => Ok(()),
+            false => Err(ChimeraCompileError::new(
+                &format!(
+                    "{} is not a valid value for the request option {}",
+                    literal, key_value.key
+                ),
+                line_col,
+            )),
+        }
+    }
+
+    fn parse_rule_to_eventually_block(
+        pair: Pair<Rule>,
+    ) -> Result<EventuallyBlock, ChimeraCompileError> {
+        // An EventuallyBlock is going to contain
+        // 1. Optional list of KeyValuePair, which look like `timeout=>10`
+        // 2. Zero or more statements which are run until they all pass
+        if pair.as_rule() != Rule::EventuallyBlock {
+            return Err(ChimeraCompileError::new(
+                "Did not get a valid EVENTUALLY block",
+                pair.line_col(),
+            ));
+        }
+        let mut key_val_pairs: Vec<KeyValuePair> = Vec::new();
+        let mut statements: Vec<Statement> = Vec::new();
+        for inner in pair.into_inner() {
+            match inner.as_rule() {
+                Rule::KeyValuePair => {
+                    key_val_pairs.push(ChimeraScriptAST::parse_rule_to_key_value_pair(inner)?)
+                }
+                Rule::Statement => statements.push(ChimeraScriptAST::pair_to_statement(inner)?),
+                _ => {
+                    return Err(ChimeraCompileError::new(
+                        "Got an invalid rule inside an EVENTUALLY block",
+                        inner.line_col(),
+                    ))
+                }
+            }
+        }
+        Ok(EventuallyBlock {
+            key_val_pairs,
+            statements,
+        })
+    }
+
+    fn parse_rule_to_assert_error_block(
+        pair: Pair<Rule>,
+    ) -> Result<AssertErrorBlock, ChimeraCompileError> {
+        // An AssertErrorBlock is going to contain
+        // 1. The name of a ChimeraRuntimeFailure variant
+        // 2. Zero or more statements, one of which should raise that error
+        if pair.as_rule() != Rule::AssertErrorBlock {
+            return Err(ChimeraCompileError::new(
+                "Did not get a valid ASSERT ERROR block",
+                pair.line_col(),
+            ));
+        }
+        let mut pairs = pair.into_inner();
+        let variant_pair = pairs
+            .next()
+            .ok_or_else(|| no_pairs_panic("AssertErrorBlock variant"))?;
+        let variant = variant_pair.as_str().to_owned();
+        if !ChimeraRuntimeFailure::VARIANT_NAMES.contains(&variant.as_str()) {
+            return Err(ChimeraCompileError::new(
+                format!(
+                    "'{}' is not an error which can be asserted, expected one of {}",
+                    variant,
+                    ChimeraRuntimeFailure::VARIANT_NAMES.join(", ")
+                )
+                .as_str(),
+                variant_pair.line_col(),
+            ));
+        }
+        let mut statements: Vec<Statement> = Vec::new();
+        for inner in pairs {
+            statements.push(ChimeraScriptAST::pair_to_statement(inner)?);
+        }
+        Ok(AssertErrorBlock {
+            variant,
+            statements,
+        })
+    }
+
     fn parse_rule_to_http_command(pair: Pair<Rule>) -> Result<Expression, ChimeraCompileError> {
         if pair.as_rule() != Rule::HttpCommand {
             return Err(ChimeraCompileError::new(
@@ -591,25 +1194,10 @@ impl ChimeraScriptAST {
         while http_pairs.peek().is_some()
             && http_pairs.peek().unwrap().as_rule() == Rule::KeyValuePair
         {
-            let mut key_value_pairs = http_pairs.next().unwrap().into_inner();
-
-            let assignment_token = key_value_pairs
-                .next()
-                .ok_or_else(|| no_pairs_panic("KeyValuePair"))?;
-            if assignment_token.as_rule() != Rule::VariableNameAssignment {
-                return Err(ChimeraCompileError::new(
-                    "Did not get a valid key for a key value pair",
-                    assignment_token.line_col(),
-                ));
-            }
-            let key = assignment_token.as_str().to_owned();
-
-            let value_token = key_value_pairs
-                .next()
-                .ok_or_else(|| no_pairs_panic("KeyValuePair"))?;
-            let value = ChimeraScriptAST::parse_rule_to_value(value_token)?;
-
-            let key_value = KeyValuePair { key, value };
+            let key_value_pair = http_pairs.next().unwrap();
+            let line_col = key_value_pair.line_col();
+            let key_value = ChimeraScriptAST::parse_rule_to_key_value_pair(key_value_pair)?;
+            ChimeraScriptAST::check_request_option(&key_value, line_col)?;
             key_val_pairs.push(key_value);
         }
         Ok(Expression::HttpCommand(HttpCommand {
@@ -633,6 +1221,8 @@ impl ChimeraScriptAST {
         //   4. Optional list of KeyValuePair, which look like `timeout=>60`
         // c. A LIST expression
         // d. A formatted string expression
+        // e. A MATCH expression which contains a value and a regex pattern
+        // f. A TYPEOF expression which contains a value
         if pair.as_rule() != Rule::Expression {
             return Err(ChimeraCompileError::new(
                 "Did not get a valid expression",
@@ -737,6 +1327,28 @@ impl ChimeraScriptAST {
             Rule::FormattedString => Ok(Expression::FormattedString(
                 Self::parse_rule_to_formatted_string(first_token)?,
             )),
+            Rule::TypeOfExpression => {
+                let value_pair = first_token
+                    .into_inner()
+                    .next()
+                    .ok_or_else(|| no_pairs_panic("TypeOfExpression"))?;
+                Ok(Expression::TypeOf(Self::parse_rule_to_value(value_pair)?))
+            }
+            Rule::MatchExpression => {
+                // A MatchExpression is going to contain
+                // 1. A value to match against
+                // 2. A regex pattern
+                let mut match_pairs = first_token.into_inner();
+                let value_pair = match_pairs
+                    .next()
+                    .ok_or_else(|| no_pairs_panic("MatchExpression's value"))?;
+                let value = Self::parse_rule_to_value(value_pair)?;
+                let pattern_pair = match_pairs
+                    .next()
+                    .ok_or_else(|| no_pairs_panic("MatchExpression's pattern"))?;
+                let pattern = Self::parse_rule_to_pattern(pattern_pair)?;
+                Ok(Expression::Match(value, pattern))
+            }
             _ => Err(ChimeraCompileError::new(
                 "Did not get a valid expression",
                 first_token.line_col(),
@@ -778,6 +1390,25 @@ impl ChimeraScriptAST {
     }
 }
 
+/// Rows of variables a `[params=name]` test function is run once for each of
+#[derive(Debug)]
+pub struct ParamsBlock {
+    pub name: String,
+    pub source: ParamsSource,
+    /// The line of the file the block is declared on, which errors loading its rows are reported on
+    pub line: i32,
+}
+
+#[derive(Debug)]
+pub enum ParamsSource {
+    Table {
+        columns: Vec<String>,
+        rows: Vec<Vec<Literal>>,
+    },
+    /// A CSV or JSON file, relative to the test file
+    File(String),
+}
+
 #[derive(Debug)]
 pub enum Decorator {
     Key(String),
@@ -801,6 +1432,8 @@ pub struct Function {
     decorators: Vec<Decorator>,
     pub name: String,
     pub block: Vec<BlockContents>,
+    timeout: Option<Duration>,
+    retries: u32,
 }
 
 impl Function {
@@ -818,6 +1451,66 @@ impl Function {
         false
     }
 
+    pub fn get_value(&self, checked_key: &str) -> Option<&str> {
+        self.decorators
+            .iter()
+            .find_map(|decorator| match decorator {
+                Decorator::KeyValue((key, value)) if key.as_str() == checked_key => {
+                    Some(value.as_str())
+                }
+                _ => None,
+            })
+    }
+
+    /// Why the function should be skipped without running it, if one of its skip decorators applies.
+    /// `[skip-if=ENV_VAR]` applies when the environment variable is set to anything other than ""
+    pub fn skip_reason(&self) -> Option<String> {
+        if self.has_key("skip") {
+            return Some("skipped by the [skip] decorator".to_owned());
+        }
+        if let Some(reason) = self.get_value("skip") {
+            return Some(reason.to_owned());
+        }
+        match self.get_value("skip-if") {
+            Some(env_var) if std::env::var(env_var).is_ok_and(|value| !value.is_empty()) => {
+                Some(format!("{} is set", env_var))
+            }
+            _ => None,
+        }
+    }
+
+    /// How long a `[timeout=N]` test is given to finish, including the cases nested inside of it
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// How many more times a `[retry=N]` test is run after it does not pass, 0 without the decorator
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    /// The tags given by a `[tags=a|b]` decorator, not including any inherited from a parent
+    pub fn tags(&self) -> Vec<&str> {
+        self.get_list("tags")
+    }
+
+    /// The names of the cases this one depends on, from a decorator like `[depends-on=login|create-user]`
+    pub fn dependencies(&self) -> Vec<&str> {
+        self.get_list("depends-on")
+    }
+
+    // The values of a key=value decorator separated by '|'
+    fn get_list(&self, checked_key: &str) -> Vec<&str> {
+        match self.get_value(checked_key) {
+            Some(values) => values
+                .split('|')
+                .map(|value| value.trim())
+                .filter(|value| !value.is_empty())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
     pub fn is_expected_failure(&self) -> bool {
         self.has_key("expected-failure")
     }
@@ -829,14 +1522,44 @@ impl Function {
     pub fn is_test_function(&self) -> bool {
         self.has_key("test")
     }
+
+    /// Which file hook the function is, if it has one of the `HOOK_KEYS` decorators. A hook is run
+    /// around the tests of its file rather than as a test itself
+    pub fn hook(&self) -> Option<&'static str> {
+        HOOK_KEYS.into_iter().find(|key| self.has_key(key))
+    }
 }
 
+/// The decorators marking a top-level case as a hook for the tests in its file
+pub const HOOK_KEYS: [&str; 4] = ["before-all", "before-each", "after-each", "after-all"];
+
 #[derive(Debug)]
 pub enum Statement {
     AssignmentExpr(AssignmentExpr),
     AssertCommand(AssertCommand),
     PrintCommand(Value),
+    SleepCommand(Value),
+    SkipCommand(Value),
+    FailCommand(Value),
     Expression(Expression),
+    Eventually(EventuallyBlock),
+    AssertError(AssertErrorBlock),
+}
+
+/// A block of statements which is re-run until every statement in it passes, or until it runs out
+/// of time or attempts
+#[derive(Debug)]
+pub struct EventuallyBlock {
+    pub key_val_pairs: Vec<KeyValuePair>,
+    pub statements: Vec<Statement>,
+}
+
+/// A block of statements which passes only if one of them raises the named error
+#[derive(Debug)]
+pub struct AssertErrorBlock {
+    /// A variant name from `ChimeraRuntimeFailure::get_variant_name`
+    pub variant: String,
+    pub statements: Vec<Statement>,
 }
 
 #[derive(Debug)]
@@ -851,12 +1574,19 @@ pub enum Expression {
     HttpCommand(HttpCommand),
     List(ListExpression),
     FormattedString(Vec<Value>),
+    /// A value and the regex pattern it is matched against
+    Match(Value, Value),
+    TypeOf(Value),
 }
 
 #[derive(Debug)]
 pub struct AssertCommand {
+    /// Written as an EXPECT, a failure is recorded and the test keeps running
+    pub soft: bool,
     pub negate_assertion: bool,
     pub subcommand: AssertSubCommand,
+    /// Lists are compared without regard to the order of their members
+    pub unordered: bool,
     pub left_value: Value,
     pub right_value: Value,
     pub error_message: Option<Value>,
@@ -876,6 +1606,8 @@ pub enum Value {
     Literal(Literal),
     Variable(String),
     FormattedString(Vec<Value>),
+    /// Values written as a list inline, e.g. the codes in `ASSERT STATUS (res) IN [200, 204]`
+    List(Vec<Value>),
 }
 
 impl std::str::FromStr for Value {
@@ -891,6 +1623,15 @@ impl std::fmt::Display for Value {
             Value::Literal(literal) => write!(f, "{}", literal),
             Value::Variable(var_name) => write!(f, "{}", var_name),
             Value::FormattedString(formatted_string) => write!(f, "{:?}", formatted_string),
+            Value::List(values) => write!(
+                f,
+                "[{}]",
+                values
+                    .iter()
+                    .map(|value| value.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
         }
     }
 }
@@ -900,6 +1641,7 @@ impl Value {
         match self {
             Value::Literal(literal) => format!("value '{}'", literal),
             Value::Variable(var_name) => format!("var '{}'", var_name.to_owned()),
+            Value::List(_) => format!("list {}", self),
             Value::FormattedString(_) => {
                 // If the resolve here fails, we don't want to swap out the error string for an
                 // "internal error" message as it will result in an error message like
@@ -931,14 +1673,32 @@ impl Value {
                             let as_string = binding.to_string();
                             built_str.push_str(as_string.as_str());
                         },
-                        Self::FormattedString(_) => return Err(ChimeraRuntimeFailure::InternalError("building a formatted string, got a formatted string inside a formatted string".to_owned()))
+                        Self::FormattedString(_) => return Err(ChimeraRuntimeFailure::InternalError("building a formatted string, got a formatted string inside a formatted string".to_owned())),
+                        Self::List(_) => return Err(ChimeraRuntimeFailure::InternalError("building a formatted string, got a list inside a formatted string".to_owned()))
                     }
                 }
                 Ok(Data::from_literal(Literal::String(built_str)))
             }
+            Value::List(values) => {
+                let mut members: Vec<Data> = Vec::with_capacity(values.len());
+                for value in values {
+                    members.push(value.resolve(context)?);
+                }
+                Ok(Data::from_vec(members))
+            }
         }
     }
 
+    /// Resolve this value as a string and compile it into a regular expression
+    pub fn resolve_regex(&self, context: &Context) -> Result<Regex, ChimeraRuntimeFailure> {
+        let resolved = self.resolve(context)?;
+        let binding = resolved.borrow()?;
+        let pattern = binding.try_into_string(self.error_print(context), context)?;
+        Regex::new(pattern).map_err(|_| {
+            ChimeraRuntimeFailure::InvalidRegex(pattern.to_owned(), context.current_line)
+        })
+    }
+
     fn get_from_var_map(context: &Context, var_name: &str) -> Result<Data, ChimeraRuntimeFailure> {
         let accessors: Vec<&str> = var_name.split('.').collect();
         let variable_map = context.get_var_map();
@@ -962,6 +1722,28 @@ pub enum AssertSubCommand {
     Status,
     Length,
     Contains,
+    Matches,
+    Schema,
+    Type,
+    /// The left value must be contained in the right, with objects allowed to have extra keys
+    Subset,
+    /// An HTTP response which must have the named header, with a value equal to the right value or
+    /// matching it as a regular expression
+    Header {
+        name: Value,
+        matches: bool,
+    },
+    /// Data which must equal a snapshot stored next to the test file. Each ignored value is a path,
+    /// or a list of paths, to a field which is left out of the comparison
+    Snapshot {
+        ignore: Vec<Value>,
+    },
+    /// Numbers, or lists of numbers, which must be within a tolerance of each other. A relative
+    /// tolerance is a fraction of the larger of the two numbers
+    Approx {
+        tolerance: Value,
+        relative: bool,
+    },
 }
 
 impl std::fmt::Display for AssertSubCommand {
@@ -975,6 +1757,22 @@ impl std::fmt::Display for AssertSubCommand {
             AssertSubCommand::Status => write!(f, "have a status code of"),
             AssertSubCommand::Length => write!(f, "have a length of"),
             AssertSubCommand::Contains => write!(f, "to contain"),
+            AssertSubCommand::Matches => write!(f, "match the pattern"),
+            AssertSubCommand::Schema => write!(f, "match the schema"),
+            AssertSubCommand::Type => write!(f, "be of type"),
+            AssertSubCommand::Subset => write!(f, "be a subset of"),
+            AssertSubCommand::Snapshot { .. } => write!(f, "match the snapshot"),
+            AssertSubCommand::Header { name, matches } => match matches {
+                true => write!(f, "have a '{}' header matching", name),
+                false => write!(f, "have a '{}' header of", name),
+            },
+            AssertSubCommand::Approx {
+                tolerance,
+                relative,
+            } => match relative {
+                true => write!(f, "be within a relative tolerance of {} of", tolerance),
+                false => write!(f, "be within {} of", tolerance),
+            },
         }
     }
 }
@@ -986,9 +1784,7 @@ pub struct HttpCommand {
     pub query_params: Vec<HttpAssignment>,
     pub http_assignments: Vec<HttpAssignment>,
     pub headers: Vec<HttpAssignment>,
-    // TODO: REMOVE THIS WHEN IMPLEMENTING KV PAIRS
-    #[allow(dead_code)]
-    key_val_pairs: Vec<KeyValuePair>,
+    pub key_val_pairs: Vec<KeyValuePair>,
 }
 
 impl From<Statement> for HttpCommand {
@@ -1004,11 +1800,12 @@ impl From<Statement> for HttpCommand {
 }
 
 impl HttpCommand {
-    pub fn resolve_path(&self, context: &Context) -> Result<String, ChimeraRuntimeFailure> {
-        let client = CLIENT
-            .get()
-            .expect("Failed to get web client while resolving an HTTP expression");
-        let mut resolved_path: String = client.get_domain().to_owned();
+    pub fn resolve_path(
+        &self,
+        domain: &str,
+        context: &Context,
+    ) -> Result<String, ChimeraRuntimeFailure> {
+        let mut resolved_path: String = domain.to_owned();
         for portion in &self.path {
             match portion.resolve(context)?.borrow()?.deref() {
                 DataKind::Literal(literal) => resolved_path.push_str(literal.to_string().as_str()),
@@ -1073,6 +1870,86 @@ impl HttpCommand {
         }
         Ok(headers)
     }
+    /// Get how this request is made, which is the given default retry policy with any key value
+    /// pairs applied on top of it. Each key was checked when the script was compiled, but values
+    /// held by variables can only be checked here
+    pub fn resolve_request_options(
+        &self,
+        context: &Context,
+        default: &RetryPolicy,
+    ) -> Result<RequestOptions, ChimeraRuntimeFailure> {
+        let mut options = RequestOptions {
+            retry_policy: default.clone(),
+            timeout: None,
+        };
+        for pair in &self.key_val_pairs {
+            let data = pair.value.resolve(context)?;
+            let borrowed = data.borrow()?;
+            let invalid_value = || {
+                ChimeraRuntimeFailure::UnknownOption(
+                    format!("{}=>{}", pair.key, borrowed),
+                    context.current_line,
+                )
+            };
+            match pair.key.as_str() {
+                "retries" => {
+                    let count = borrowed.try_into_u64(&pair.value, context)?;
+                    options.retry_policy.count = u32::try_from(count).unwrap_or(u32::MAX);
+                }
+                "backoff" => {
+                    let backoff = borrowed
+                        .try_into_number_kind(&pair.value, context)?
+                        .to_f64();
+                    if !backoff.is_finite() || backoff < 0.0 {
+                        return Err(ChimeraRuntimeFailure::InvalidDuration(
+                            format!("backoff=>{}", backoff),
+                            context.current_line,
+                        ));
+                    }
+                    options.retry_policy.backoff = backoff;
+                }
+                "retry_on" => {
+                    let kinds =
+                        borrowed.try_into_string(pair.value.error_print(context), context)?;
+                    options.retry_policy.retry_on =
+                        parse_retry_on(kinds).ok_or_else(invalid_value)?;
+                }
+                "timeout" => {
+                    let seconds = borrowed
+                        .try_into_number_kind(&pair.value, context)?
+                        .to_f64();
+                    let timeout = Duration::try_from_secs_f64(seconds).map_err(|_| {
+                        ChimeraRuntimeFailure::InvalidDuration(
+                            format!("timeout=>{}", seconds),
+                            context.current_line,
+                        )
+                    })?;
+                    options.timeout = Some(timeout);
+                }
+                _ => return Err(invalid_value()),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// The key value pairs an HTTP request accepts, like `retries=>3`
+pub const REQUEST_OPTIONS: [&str; 4] = ["retries", "backoff", "retry_on", "timeout"];
+
+/// How a single HTTP request is made
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestOptions {
+    pub retry_policy: RetryPolicy,
+    /// How long each attempt of the request can take, in addition to any test deadline
+    pub timeout: Option<Duration>,
+}
+
+/// Parse a comma separated list of the kinds of failures to retry, like "connect,timeout"
+fn parse_retry_on(kinds: &str) -> Option<Vec<TransportError>> {
+    kinds
+        .split(',')
+        .map(|kind| kind.trim().parse().ok())
+        .collect()
 }
 
 #[derive(Debug)]
@@ -1082,11 +1959,9 @@ pub struct HttpAssignment {
 }
 
 #[derive(Debug)]
-// TODO: REMOVE ALLOW DEAD CODE WHEN IMPLEMENTING KV PAIRS, HERE AND IN HTTP COMMAND STRUCT
-#[allow(dead_code)]
 pub struct KeyValuePair {
-    key: String,
-    value: Value,
+    pub key: String,
+    pub value: Value,
 }
 
 #[derive(Debug)]
@@ -1141,6 +2016,7 @@ Here be testing
  */
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod ast_tests {
     use super::*;
     use crate::frontend::CScriptTokenPairs;
@@ -1497,7 +2373,7 @@ mod ast_tests {
         );
 
         // HTTP command with a path, query params, body params, and key/value pairs
-        let full_expression: HttpCommand = str_to_statement("GET /foo/bar/baz?foo=5&another=\"bar\" some_num=5 some_str=\"value\" timeout=>60 boolKey=>false;").into();
+        let full_expression: HttpCommand = str_to_statement("GET /foo/bar/baz?foo=5&another=\"bar\" some_num=5 some_str=\"value\" timeout=>60 retries=>2;").into();
         assert_eq!(full_expression.verb, HTTPVerb::Get);
         assert_eq!(
             full_expression.path,
@@ -1712,4 +2588,525 @@ mod ast_tests {
             "Expected a statement with no EndOf to fail to parse"
         );
     }
+
+    #[test]
+    /// Test that regex patterns are taken as written, without needing to escape parentheses
+    fn regex_patterns() {
+        let assert_command: AssertCommand =
+            str_to_statement(r#"ASSERT MATCHES (id) "^usr_(\d+)\"$" "Bad id";"#).into();
+        assert_eq!(assert_command.subcommand, AssertSubCommand::Matches);
+        assert_eq!(
+            assert_command.right_value,
+            Value::Literal(Literal::String(r#"^usr_(\d+)"$"#.to_owned()))
+        );
+        assert_eq!(
+            assert_command.error_message,
+            Some(Value::Literal(Literal::String("Bad id".to_owned())))
+        );
+
+        match str_to_statement(r#"var m = MATCH (res.body.id) (pattern);"#) {
+            Statement::AssignmentExpr(assignment) => {
+                assert_eq!(assignment.var_name, "m");
+                match assignment.expression {
+                    Expression::Match(value, pattern) => {
+                        assert_eq!(value, Value::Variable("res.body.id".to_owned()));
+                        assert_eq!(pattern, Value::Variable("pattern".to_owned()));
+                    }
+                    _ => panic!("A MATCH expression did not resolve to the correct variant."),
+                }
+            }
+            _ => panic!("Statement for a MATCH assignment did not resolve to the correct variant."),
+        }
+    }
+
+    #[test]
+    /// Test that request options are checked when a script is compiled, on the line they are written
+    fn request_options() {
+        let file = |request: &str| format!("[test]\ncase a() {{\n  PRINT 1;\n  {};\n}}\n", request);
+        let valid = ChimeraScriptAST::new(&file(
+            "GET /foo timeout=>60 retries=>2 backoff=>0.5 retry_on=>\"connect,timeout\"",
+        ));
+        assert!(valid.is_ok(), "Expected every request option to be valid");
+        // A value held by a variable can only be checked when the request is made
+        assert!(ChimeraScriptAST::new(&file("GET /foo retries=>(count)")).is_ok());
+        for (request, message) in [
+            (
+                "GET /foo boolKey=>false",
+                "'boolKey' is not a valid request option",
+            ),
+            (
+                "GET /foo retries=>-1",
+                "-1 is not a valid value for the request option retries",
+            ),
+            (
+                "GET /foo timeout=>\"soon\"",
+                "soon is not a valid value for the request option timeout",
+            ),
+            (
+                "GET /foo backoff=>-0.5",
+                "-0.5 is not a valid value for the request option backoff",
+            ),
+            (
+                "GET /foo retry_on=>\"refused\"",
+                "refused is not a valid value for the request option retry_on",
+            ),
+        ] {
+            match ChimeraScriptAST::new(&file(request)) {
+                Err(error) => {
+                    let error = format!("{:?}", error);
+                    assert!(error.contains(message), "Got {} for {}", error, request);
+                    assert!(error.contains("line: 4"), "Got {} for {}", error, request);
+                }
+                Ok(_) => panic!("Expected {} to fail to compile", request),
+            }
+        }
+    }
+
+    #[test]
+    /// Test that a variable name is only the characters of the name. Without an atomic rule the
+    /// whitespace after a single character name became part of it, so `var m` could never be read
+    fn variable_names() {
+        for name in ["m", "foo", "my_var"] {
+            match str_to_statement(&format!("var {} = LITERAL 1;", name)) {
+                Statement::AssignmentExpr(assignment) => assert_eq!(assignment.var_name, name),
+                _ => panic!("Statement for an assignment did not resolve to the correct variant."),
+            }
+        }
+        // Whitespace cannot be used to join two words into one name
+        let file = "[test]\ncase names() {\n  var a b = LITERAL 1;\n}\n";
+        assert!(ChimeraScriptAST::new(file).is_err());
+    }
+
+    #[test]
+    /// Test APPROX assertions with absolute and relative tolerances
+    fn approx_assertions() {
+        let assert_command: AssertCommand =
+            str_to_statement("ASSERT APPROX (total) 0.3 (tolerance);").into();
+        assert_eq!(
+            assert_command.subcommand,
+            AssertSubCommand::Approx {
+                tolerance: Value::Variable("tolerance".to_owned()),
+                relative: false
+            }
+        );
+        assert_eq!(
+            assert_command.right_value,
+            Value::Literal(Literal::Number(NumberKind::F64(0.3)))
+        );
+
+        let assert_command: AssertCommand =
+            str_to_statement(r#"ASSERT NOT APPROX (mean) 100 0.05 RELATIVE "Too far";"#).into();
+        assert!(assert_command.negate_assertion);
+        assert_eq!(
+            assert_command.subcommand,
+            AssertSubCommand::Approx {
+                tolerance: Value::Literal(Literal::Number(NumberKind::F64(0.05))),
+                relative: true
+            }
+        );
+        assert_eq!(
+            assert_command.error_message,
+            Some(Value::Literal(Literal::String("Too far".to_owned())))
+        );
+    }
+
+    #[test]
+    /// Test that a SLEEP statement parses into a value
+    fn sleep_statement() {
+        match str_to_statement("SLEEP 1.5;") {
+            Statement::SleepCommand(val) => {
+                assert_eq!(val, Value::Literal(Literal::Number(NumberKind::F64(1.5))))
+            }
+            _ => panic!("Statement for a SLEEP did not resolve to the correct variant."),
+        }
+    }
+
+    #[test]
+    /// Test that an EVENTUALLY block parses its options and the statements inside of it
+    fn eventually_block() {
+        match str_to_statement(
+            "EVENTUALLY timeout=>10 interval=>0.5 { var r = GET /items; ASSERT STATUS (r) 200; }",
+        ) {
+            Statement::Eventually(eventually_block) => {
+                assert_eq!(eventually_block.key_val_pairs.len(), 2);
+                assert_eq!(eventually_block.key_val_pairs[0].key, "timeout");
+                assert_eq!(
+                    eventually_block.key_val_pairs[1].value,
+                    Value::Literal(Literal::Number(NumberKind::F64(0.5)))
+                );
+                assert_eq!(eventually_block.statements.len(), 2);
+                assert!(matches!(
+                    eventually_block.statements[1],
+                    Statement::AssertCommand(_)
+                ));
+            }
+            _ => {
+                panic!("Statement for an EVENTUALLY block did not resolve to the correct variant.")
+            }
+        }
+
+        // Statements inside of the block still need an EndOf
+        let failure_res = std::panic::catch_unwind(|| {
+            str_to_statement("EVENTUALLY { ASSERT EQUALS 1 1 }");
+        });
+        assert!(
+            failure_res.is_err(),
+            "Expected an EVENTUALLY block containing a statement with no EndOf to fail to parse"
+        );
+    }
+
+    #[test]
+    /// Test SUBSET assertions and the UNORDERED modifier
+    fn subset_assertions() {
+        let assert_command: AssertCommand =
+            str_to_statement("ASSERT SUBSET (expected) (res.body);").into();
+        assert_eq!(assert_command.subcommand, AssertSubCommand::Subset);
+        assert!(!assert_command.unordered);
+
+        let assert_command: AssertCommand =
+            str_to_statement("ASSERT NOT SUBSET UNORDERED (expected) (res.body);").into();
+        assert_eq!(assert_command.subcommand, AssertSubCommand::Subset);
+        assert!(assert_command.negate_assertion);
+        assert!(assert_command.unordered);
+
+        let assert_command: AssertCommand =
+            str_to_statement("ASSERT EQUALS UNORDERED (ids) (expected);").into();
+        assert_eq!(assert_command.subcommand, AssertSubCommand::Equals);
+        assert!(assert_command.unordered);
+        let assert_command: AssertCommand =
+            str_to_statement("ASSERT CONTAINS UNORDERED (pairs) (pair);").into();
+        assert_eq!(assert_command.subcommand, AssertSubCommand::Contains);
+        assert!(assert_command.unordered);
+
+        let failure_res = std::panic::catch_unwind(|| {
+            str_to_statement("ASSERT GT UNORDERED 1 1;");
+        });
+        assert!(
+            failure_res.is_err(),
+            "Expected UNORDERED to fail to parse with a subcommand which does not support it"
+        );
+    }
+
+    #[test]
+    /// Test SNAPSHOT assertions with and without a list of ignored paths
+    fn snapshot_assertions() {
+        let assert_command: AssertCommand =
+            str_to_statement(r#"ASSERT SNAPSHOT (res.body) "get_user";"#).into();
+        assert_eq!(
+            assert_command.subcommand,
+            AssertSubCommand::Snapshot { ignore: Vec::new() }
+        );
+        assert_eq!(
+            assert_command.right_value,
+            Value::Literal(Literal::String("get_user".to_owned()))
+        );
+
+        let assert_command: AssertCommand = str_to_statement(
+            r#"ASSERT SNAPSHOT (res.body) "get_user" IGNORE ["id", (volatile)] "Changed";"#,
+        )
+        .into();
+        assert_eq!(
+            assert_command.subcommand,
+            AssertSubCommand::Snapshot {
+                ignore: vec![
+                    Value::Literal(Literal::String("id".to_owned())),
+                    Value::Variable("volatile".to_owned())
+                ]
+            }
+        );
+        assert_eq!(
+            assert_command.error_message,
+            Some(Value::Literal(Literal::String("Changed".to_owned())))
+        );
+
+        let assert_command: AssertCommand =
+            str_to_statement(r#"ASSERT SNAPSHOT (res.body) "get_user" IGNORE (volatile);"#).into();
+        assert_eq!(
+            assert_command.subcommand,
+            AssertSubCommand::Snapshot {
+                ignore: vec![Value::Variable("volatile".to_owned())]
+            }
+        );
+    }
+
+    #[test]
+    /// Test STATUS assertions with status classes and lists, and HEADER assertions
+    fn status_and_header_assertions() {
+        let assert_command: AssertCommand = str_to_statement("ASSERT STATUS (res) 2xx;").into();
+        assert_eq!(assert_command.subcommand, AssertSubCommand::Status);
+        assert_eq!(
+            assert_command.right_value,
+            Value::Literal(Literal::String("2xx".to_owned()))
+        );
+
+        let assert_command: AssertCommand =
+            str_to_statement("ASSERT STATUS (res) IN [200, 3XX, (code)];").into();
+        assert_eq!(
+            assert_command.right_value,
+            Value::List(vec![
+                Value::Literal(Literal::Number(NumberKind::U64(200))),
+                Value::Literal(Literal::String("3xx".to_owned())),
+                Value::Variable("code".to_owned()),
+            ])
+        );
+
+        let assert_command: AssertCommand =
+            str_to_statement(r#"ASSERT HEADER (res) "content-type" "application/json";"#).into();
+        assert_eq!(
+            assert_command.subcommand,
+            AssertSubCommand::Header {
+                name: Value::Literal(Literal::String("content-type".to_owned())),
+                matches: false
+            }
+        );
+        assert_eq!(
+            assert_command.right_value,
+            Value::Literal(Literal::String("application/json".to_owned()))
+        );
+
+        let assert_command: AssertCommand =
+            str_to_statement(r#"ASSERT NOT HEADER (res) (name) MATCHES "^W/\d" "Bad etag";"#)
+                .into();
+        assert!(assert_command.negate_assertion);
+        assert_eq!(
+            assert_command.subcommand,
+            AssertSubCommand::Header {
+                name: Value::Variable("name".to_owned()),
+                matches: true
+            }
+        );
+        assert_eq!(
+            assert_command.right_value,
+            Value::Literal(Literal::String(r#"^W/\d"#.to_owned()))
+        );
+        assert_eq!(
+            assert_command.error_message,
+            Some(Value::Literal(Literal::String("Bad etag".to_owned())))
+        );
+    }
+
+    #[test]
+    /// Test that an EXPECT parses to a soft assertion
+    fn expect_statement() {
+        let assert_command: AssertCommand = str_to_statement("ASSERT EQUALS 1 1;").into();
+        assert!(!assert_command.soft);
+        let assert_command: AssertCommand =
+            str_to_statement(r#"EXPECT NOT EQUALS (foo) 1 "Soft";"#).into();
+        assert!(assert_command.soft);
+        assert!(assert_command.negate_assertion);
+        assert_eq!(assert_command.subcommand, AssertSubCommand::Equals);
+        assert_eq!(
+            assert_command.error_message,
+            Some(Value::Literal(Literal::String("Soft".to_owned())))
+        );
+    }
+
+    #[test]
+    /// Test that an ASSERT ERROR block parses the error name and the statements inside of it
+    fn assert_error_block() {
+        match str_to_statement("ASSERT ERROR OutOfBounds { ASSERT EQUALS (list.5) 5; PRINT 1; }") {
+            Statement::AssertError(assert_error_block) => {
+                assert_eq!(assert_error_block.variant, "OutOfBounds");
+                assert_eq!(assert_error_block.statements.len(), 2);
+                assert!(matches!(
+                    assert_error_block.statements[0],
+                    Statement::AssertCommand(_)
+                ));
+            }
+            _ => {
+                panic!(
+                    "Statement for an ASSERT ERROR block did not resolve to the correct variant."
+                )
+            }
+        }
+
+        // Only errors which exist can be expected
+        let failure_res = std::panic::catch_unwind(|| {
+            str_to_statement("ASSERT ERROR NotAnError { PRINT 1; }");
+        });
+        assert!(
+            failure_res.is_err(),
+            "Expected an ASSERT ERROR block with an unknown error to fail to parse"
+        );
+    }
+
+    #[test]
+    /// Test that SKIP and FAIL statements parse their reasons
+    fn skip_and_fail_statements() {
+        match str_to_statement(r#"SKIP "disabled";"#) {
+            Statement::SkipCommand(val) => {
+                assert_eq!(val, Value::Literal(Literal::String("disabled".to_owned())))
+            }
+            _ => panic!("Statement for a SKIP did not resolve to the correct variant."),
+        }
+        match str_to_statement("FAIL (reason);") {
+            Statement::FailCommand(val) => assert_eq!(val, Value::Variable("reason".to_owned())),
+            _ => panic!("Statement for a FAIL did not resolve to the correct variant."),
+        }
+    }
+
+    #[test]
+    /// Test that key value decorators accept quoted values
+    fn key_value_decorators() {
+        let ast = ChimeraScriptAST::new(
+            "[test, skip=\"not deployed, yet\", skip-if=SOME_VAR]\ncase skipped() {\n  PRINT 1;\n}",
+        )
+        .expect("Failed to parse a function with key value decorators");
+        let function = &ast.functions[0];
+        assert!(function.is_test_function());
+        assert_eq!(function.get_value("skip"), Some("not deployed, yet"));
+        assert_eq!(function.get_value("skip-if"), Some("SOME_VAR"));
+        assert_eq!(function.get_value("test"), None);
+        assert_eq!(function.skip_reason(), Some("not deployed, yet".to_owned()));
+    }
+
+    #[test]
+    /// Test that PARAMS blocks parse tables and files, and rows must match the columns
+    fn params_blocks() {
+        let ast = ChimeraScriptAST::new(
+            "PARAMS rows {\n  name, count;\n  \"foo\", 1;\n  \"bar\", 2.5;\n}\nPARAMS users FROM \"users.csv\";\n[test, params=rows]\ncase params() {\n  PRINT (name);\n}",
+        )
+        .expect("Failed to parse PARAMS blocks");
+        assert_eq!(ast.params.len(), 2);
+        assert_eq!(ast.params[0].name, "rows");
+        match &ast.params[0].source {
+            ParamsSource::Table { columns, rows } => {
+                assert_eq!(columns, &vec!["name".to_owned(), "count".to_owned()]);
+                assert_eq!(rows.len(), 2);
+                assert_eq!(rows[1][1], Literal::Number(NumberKind::F64(2.5)));
+            }
+            ParamsSource::File(_) => panic!("Expected PARAMS rows to be a table"),
+        }
+        assert!(matches!(&ast.params[1].source, ParamsSource::File(file) if file == "users.csv"));
+        assert_eq!(ast.functions[0].get_value("params"), Some("rows"));
+
+        let mismatched = ChimeraScriptAST::new(
+            "PARAMS rows {\n  name, count;\n  \"foo\";\n}\n[test]\ncase a() {\n  PRINT 1;\n}",
+        );
+        assert!(
+            mismatched.is_err(),
+            "Expected a row with the wrong number of values to fail to compile"
+        );
+        let duplicated = ChimeraScriptAST::new(
+            "PARAMS rows FROM \"a.csv\";\nPARAMS rows FROM \"b.csv\";\n[test]\ncase a() {\n  PRINT 1;\n}",
+        );
+        assert!(
+            duplicated.is_err(),
+            "Expected PARAMS defined twice to fail to compile"
+        );
+        let undefined = ChimeraScriptAST::new("[test, params=missing]\ncase a() {\n  PRINT 1;\n}");
+        assert!(
+            undefined.is_err(),
+            "Expected a test using PARAMS which are not defined to fail to compile"
+        );
+        let declared_after = ChimeraScriptAST::new(
+            "[test, params=rows]\ncase a() {\n  PRINT (name);\n}\nPARAMS rows {\n  name;\n  \"foo\";\n}",
+        )
+        .expect("Failed to parse PARAMS declared after the test using them");
+        assert_eq!(declared_after.params[0].line, 5);
+    }
+
+    #[test]
+    /// Test that cases are ordered after the cases they depend on, and invalid dependencies fail to compile
+    fn depends_on_decorators() {
+        let ast = ChimeraScriptAST::new(
+            "[test, depends-on=b|c]\ncase a() {\n  PRINT 1;\n}\n[test, depends-on=c]\ncase b() {\n  PRINT 1;\n}\n[test]\ncase c() {\n  PRINT 1;\n}\n[test]\ncase d() {\n  PRINT 1;\n}",
+        )
+        .expect("Failed to parse cases with dependencies");
+        let names: Vec<&str> = ast
+            .functions
+            .iter()
+            .map(|function| function.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["c", "b", "a", "d"]);
+        assert_eq!(ast.functions[2].dependencies(), vec!["b", "c"]);
+
+        let missing =
+            ChimeraScriptAST::new("[test, depends-on=missing]\ncase a() {\n  PRINT 1;\n}");
+        assert!(
+            missing.is_err(),
+            "Expected a dependency on a case which does not exist to fail to compile"
+        );
+        let cycle = ChimeraScriptAST::new(
+            "[test, depends-on=b]\ncase a() {\n  PRINT 1;\n}\n[test, depends-on=c]\ncase b() {\n  PRINT 1;\n}\n[test, depends-on=a]\ncase c() {\n  PRINT 1;\n}",
+        );
+        match cycle {
+            Err(error) => assert!(format!("{:?}", error).contains("a -> b -> c -> a")),
+            Ok(_) => panic!("Expected a cycle of dependencies to fail to compile"),
+        }
+        let own_dependency =
+            ChimeraScriptAST::new("[test, depends-on=a]\ncase a() {\n  PRINT 1;\n}");
+        assert!(
+            own_dependency.is_err(),
+            "Expected a case which depends on itself to fail to compile"
+        );
+        let nested = ChimeraScriptAST::new(
+            "[test]\ncase a() {\n  PRINT 1;\n}\n[test]\ncase b() {\n  [depends-on=a]\n  case c() {\n    PRINT 1;\n  }\n}",
+        );
+        assert!(
+            nested.is_err(),
+            "Expected a nested case with a dependency to fail to compile"
+        );
+    }
+
+    #[test]
+    /// Test that a timeout decorator is read when the file is compiled, and an invalid one fails to compile
+    fn timeout_decorators() {
+        let ast = ChimeraScriptAST::new(
+            "[test, timeout=\"0.5\"]\ncase a() {\n  [timeout=30]\n  case b() {\n    PRINT 1;\n  }\n}",
+        )
+        .expect("Failed to parse cases with timeouts");
+        assert_eq!(ast.functions[0].timeout(), Some(Duration::from_millis(500)));
+        match &ast.functions[0].block[0] {
+            BlockContents::Function(nested) => {
+                assert_eq!(nested.timeout(), Some(Duration::from_secs(30)))
+            }
+            _ => panic!("Expected the block to hold a nested case"),
+        }
+
+        for timeout in ["soon", "\"-1\"", "\"NaN\""] {
+            let file = format!(
+                "[test]\ncase a() {{\n  [timeout={}]\n  case b() {{\n    PRINT 1;\n  }}\n}}",
+                timeout
+            );
+            match ChimeraScriptAST::new(&file) {
+                Err(error) => {
+                    let error = format!("{:?}", error);
+                    assert!(error.contains("is not a valid timeout"), "Got {}", error);
+                    assert!(error.contains("line: 3"), "Got {}", error);
+                }
+                Ok(_) => panic!("Expected [timeout={}] to fail to compile", timeout),
+            }
+        }
+    }
+
+    #[test]
+    /// Test that a retry decorator is read when the file is compiled, and an invalid one fails to compile
+    fn retry_decorators() {
+        let ast = ChimeraScriptAST::new(
+            "[test, retry=2]\ncase a() {\n  PRINT 1;\n}\n[test]\ncase b() {\n  PRINT 1;\n}",
+        )
+        .expect("Failed to parse cases with retries");
+        assert_eq!(ast.functions[0].retries(), 2);
+        assert_eq!(ast.functions[1].retries(), 0);
+
+        for retry in ["many", "\"-1\"", "\"1.5\""] {
+            let file = format!(
+                "[test]\ncase a() {{\n  PRINT 1;\n}}\n[test, retry={}]\ncase b() {{\n  PRINT 1;\n}}",
+                retry
+            );
+            match ChimeraScriptAST::new(&file) {
+                Err(error) => {
+                    let error = format!("{:?}", error);
+                    assert!(
+                        error.contains("is not a valid retry count"),
+                        "Got {}",
+                        error
+                    );
+                    assert!(error.contains("line: 5"), "Got {}", error);
+                }
+                Ok(_) => panic!("Expected [retry={}] to fail to compile", retry),
+            }
+        }
+    }
 }
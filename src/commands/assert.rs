@@ -1,7 +1,10 @@
-use crate::abstract_syntax_tree::{AssertCommand, AssertSubCommand};
+use crate::abstract_syntax_tree::{AssertCommand, AssertSubCommand, Value};
 use crate::err_handle::{ChimeraRuntimeFailure, VarTypes};
 use crate::frontend::Context;
-use crate::literal::{Collection, DataKind};
+use crate::literal::{Collection, Data, DataKind, Literal, NumberKind, TYPE_NAMES};
+use crate::util::pretty::{pretty_print, structural_diff, subset_diff, unordered_diff};
+use crate::util::snapshot::snapshot_differences;
+use std::fs;
 use std::ops::Deref;
 
 pub fn assert_command(
@@ -12,43 +15,89 @@ pub fn assert_command(
     let left_data = left_binding.borrow()?;
     let right_binding = assert_command.right_value.resolve(context)?;
     let right_data = right_binding.borrow()?;
-    let assertion_passed = match assert_command.subcommand {
+    // Extra lines explaining why an assertion failed, printed below the failure message
+    let mut details: Vec<String> = Vec::new();
+    let assertion_passed = match &assert_command.subcommand {
         AssertSubCommand::Length => {
             let assert_len = right_data.try_into_usize(&assert_command.right_value, context)?;
             let vec =
                 left_data.try_into_list(assert_command.left_value.error_print(context), context)?;
             vec.len() == assert_len
         }
-        AssertSubCommand::Equals => left_data.deref() == right_data.deref(),
+        AssertSubCommand::Equals => match assert_command.unordered {
+            true => left_data.unordered_eq(right_data.deref())?,
+            false => left_data.deref() == right_data.deref(),
+        },
         AssertSubCommand::Status => {
-            let res = match left_data.deref() {
-                DataKind::Collection(Collection::Object(obj)) => match obj.get("status_code") {
-                    Some(status_code) => {
-                        let expected_code =
-                            right_data.try_into_u64(&assert_command.right_value, context)?;
-                        let status_as_num = status_code
-                            .borrow()?
-                            .deref()
-                            .try_into_u64(&assert_command.left_value, context)?;
-                        Some(expected_code == status_as_num)
-                    }
-                    None => None,
-                },
+            let status_code = response_field(
+                context,
+                &assert_command.left_value,
+                &left_data,
+                "status_code",
+            )?;
+            let status_as_num = status_code
+                .borrow()?
+                .deref()
+                .try_into_u64(&assert_command.left_value, context)?;
+            let passed = status_matches(
+                context,
+                &assert_command.right_value,
+                right_data.deref(),
+                status_as_num,
+            )?;
+            if !passed {
+                details.push(format!("Got status code {}", status_as_num));
+            }
+            passed
+        }
+        AssertSubCommand::Header { name, matches } => {
+            let headers =
+                response_field(context, &assert_command.left_value, &left_data, "headers")?;
+            let name_binding = name.resolve(context)?;
+            let name_borrow = name_binding.borrow()?;
+            let header_name = name_borrow
+                .try_into_string(name.error_print(context), context)?
+                .to_ascii_lowercase();
+            let headers_borrow = headers.borrow()?;
+            let header_value = match headers_borrow.deref() {
+                DataKind::Collection(Collection::Object(header_map)) => {
+                    header_map.get(&header_name).cloned()
+                }
                 _ => None,
             };
-            match res {
-                Some(b) => b,
+            match header_value {
+                Some(header_value) => {
+                    let header_borrow = header_value.borrow()?;
+                    let header_str = header_borrow
+                        .try_into_string(format!("header '{}'", header_name), context)?;
+                    let passed = match matches {
+                        true => assert_command
+                            .right_value
+                            .resolve_regex(context)?
+                            .is_match(header_str),
+                        false => {
+                            header_str
+                                == right_data.try_into_string(
+                                    assert_command.right_value.error_print(context),
+                                    context,
+                                )?
+                        }
+                    };
+                    if !passed {
+                        details.push(format!("Got header value '{}'", header_str));
+                    }
+                    passed
+                }
                 None => {
-                    return Err(ChimeraRuntimeFailure::VarWrongType(
-                        assert_command.left_value.error_print(context),
-                        VarTypes::HttpResponse,
-                        context.current_line,
-                    ))
+                    details.push(format!("The response has no '{}' header", header_name));
+                    false
                 }
             }
         }
         AssertSubCommand::Contains => match left_data.deref() {
-            DataKind::Collection(c) => c.contains(right_data, context)?,
+            DataKind::Collection(c) => {
+                c.contains(right_data.deref(), assert_command.unordered, context)?
+            }
             _ => {
                 return Err(ChimeraRuntimeFailure::VarWrongType(
                     assert_command.left_value.error_print(context),
@@ -57,6 +106,86 @@ pub fn assert_command(
                 ))
             }
         },
+        AssertSubCommand::Schema => {
+            let schema_path = right_data
+                .try_into_string(assert_command.right_value.error_print(context), context)?;
+            details = schema_violations(context, schema_path, left_data.deref())?;
+            details.is_empty()
+        }
+        AssertSubCommand::Subset => {
+            // The expected value comes first, so it reads as "expected is a subset of actual"
+            details = subset_diff(
+                right_data.deref(),
+                left_data.deref(),
+                assert_command.unordered,
+            )?;
+            details.is_empty()
+        }
+        AssertSubCommand::Snapshot { ignore } => {
+            let name = right_data
+                .try_into_string(assert_command.right_value.error_print(context), context)?;
+            let ignore_paths = resolve_ignore_paths(context, ignore)?;
+            let update = crate::UPDATE_SNAPSHOTS.get().copied().unwrap_or(false);
+            let differences =
+                snapshot_differences(context, name, left_data.deref(), &ignore_paths, update)?;
+            if !differences.is_empty() {
+                details.push("Differences from the snapshot:".to_owned());
+                details.extend(differences.into_iter().map(|d| format!("  {}", d)));
+            }
+            details.is_empty()
+        }
+        AssertSubCommand::Type => {
+            let type_name = right_data
+                .try_into_string(assert_command.right_value.error_print(context), context)?;
+            if !TYPE_NAMES.contains(&type_name) {
+                return Err(ChimeraRuntimeFailure::UnknownType(
+                    type_name.to_owned(),
+                    context.current_line,
+                ));
+            }
+            let passed = left_data.is_type(type_name);
+            if !passed {
+                details.push(format!("Got type {}", left_data.type_name()));
+            }
+            passed
+        }
+        AssertSubCommand::Matches => {
+            let regex = assert_command.right_value.resolve_regex(context)?;
+            let haystack = left_data
+                .try_into_string(assert_command.left_value.error_print(context), context)?;
+            regex.is_match(haystack)
+        }
+        AssertSubCommand::Approx {
+            tolerance,
+            relative,
+        } => {
+            let tolerance_binding = tolerance.resolve(context)?;
+            let tolerance_num = tolerance_binding
+                .borrow()?
+                .try_into_number_kind(tolerance, context)?
+                .to_f64();
+            // A negative or NaN tolerance would silently fail, or pass, every comparison
+            if !tolerance_num.is_finite() || tolerance_num < 0.0 {
+                return Err(ChimeraRuntimeFailure::VarWrongType(
+                    tolerance.error_print(context),
+                    VarTypes::Tolerance,
+                    context.current_line,
+                ));
+            }
+            let approx = Approx {
+                assert_command,
+                tolerance: tolerance_num,
+                relative: *relative,
+            };
+            approx.compare(
+                context,
+                left_data.deref(),
+                right_data.deref(),
+                "",
+                &mut details,
+            )?;
+            details.is_empty()
+        }
         _ => {
             // The remaining matches are the four relational operators, left and right must both be numbers
             let numeric_left =
@@ -89,17 +218,286 @@ pub fn assert_command(
             true => "to not",
             false => "to",
         };
-        return Err(ChimeraRuntimeFailure::TestFailure(
-            format!(
-                "{}Expected {} {} {} {}",
-                custom_error_message,
-                assert_command.left_value.error_print(context),
-                to_be_or_not_to_be,
-                assert_command.subcommand,
-                assert_command.right_value.error_print(context)
-            ),
-            context.current_line,
-        ));
+        let mut message = format!(
+            "{}Expected {} {} {} {}",
+            custom_error_message,
+            assert_command.left_value.error_print(context),
+            to_be_or_not_to_be,
+            assert_command.subcommand,
+            assert_command.right_value.error_print(context)
+        );
+        if assert_command.unordered {
+            message.push_str(" in any order");
+        }
+        for detail in details {
+            message.push_str(&format!("\n  {}", detail));
+        }
+        // Literals are already printed in the message, but variables are only printed by name.
+        // A snapshot can be a large response body, so it is left to the listed differences
+        let is_snapshot = matches!(assert_command.subcommand, AssertSubCommand::Snapshot { .. });
+        for (value, data) in [
+            (&assert_command.left_value, left_data.deref()),
+            (&assert_command.right_value, right_data.deref()),
+        ] {
+            if let (Value::Variable(_), false) = (value, is_snapshot) {
+                message.push_str(&describe_resolved(value.error_print(context), data)?);
+            }
+        }
+        if assert_command.subcommand == AssertSubCommand::Equals && !assert_command.negate_assertion
+        {
+            if let (DataKind::Collection(_), DataKind::Collection(_)) =
+                (left_data.deref(), right_data.deref())
+            {
+                let differences = match assert_command.unordered {
+                    true => unordered_diff(left_data.deref(), right_data.deref())?,
+                    false => structural_diff(left_data.deref(), right_data.deref())?,
+                };
+                message.push_str("\n  Differences from the expected value:");
+                for difference in differences {
+                    message.push_str(&format!("\n    {}", difference));
+                }
+            }
+        }
+        let failure = ChimeraRuntimeFailure::TestFailure(message, context.current_line);
+        if assert_command.soft {
+            context.record_soft_failure(failure);
+            return Ok(());
+        }
+        return Err(failure);
     }
     Ok(())
 }
+
+/// Compares numbers, or lists of numbers element-wise, within a tolerance
+struct Approx<'a> {
+    assert_command: &'a AssertCommand,
+    tolerance: f64,
+    relative: bool,
+}
+
+impl Approx<'_> {
+    // Pushes a detail for every pair of numbers not within the tolerance, `path` is the list
+    // index of the pair being compared, or empty at the top level
+    fn compare(
+        &self,
+        context: &Context,
+        left: &DataKind,
+        right: &DataKind,
+        path: &str,
+        details: &mut Vec<String>,
+    ) -> Result<(), ChimeraRuntimeFailure> {
+        if let (
+            DataKind::Collection(Collection::List(left_list)),
+            DataKind::Collection(Collection::List(right_list)),
+        ) = (left, right)
+        {
+            if left_list.len() != right_list.len() {
+                details.push(format!(
+                    "Lengths differ, got {} items but expected {}",
+                    left_list.len(),
+                    right_list.len()
+                ));
+                return Ok(());
+            }
+            for (index, (left_item, right_item)) in left_list.iter().zip(right_list).enumerate() {
+                let index_path = match path.is_empty() {
+                    true => index.to_string(),
+                    false => format!("{}.{}", path, index),
+                };
+                self.compare(
+                    context,
+                    left_item.borrow()?.deref(),
+                    right_item.borrow()?.deref(),
+                    &index_path,
+                    details,
+                )?;
+            }
+            return Ok(());
+        }
+        let left_num = left
+            .try_into_number_kind(&self.assert_command.left_value, context)?
+            .to_f64();
+        let right_num = right
+            .try_into_number_kind(&self.assert_command.right_value, context)?
+            .to_f64();
+        let allowed = match self.relative {
+            true => self.tolerance * left_num.abs().max(right_num.abs()),
+            false => self.tolerance,
+        };
+        let difference = (left_num - right_num).abs();
+        if difference > allowed {
+            let location = match path.is_empty() {
+                true => "".to_owned(),
+                false => format!("Index {}: ", path),
+            };
+            details.push(format!(
+                "{}{} differs from {} by {}, more than the {} allowed",
+                location, left_num, right_num, difference, allowed
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Get a field of an HTTP response, such as its status code or headers
+fn response_field(
+    context: &Context,
+    response_value: &Value,
+    response: &DataKind,
+    field: &str,
+) -> Result<Data, ChimeraRuntimeFailure> {
+    let found = match response {
+        DataKind::Collection(Collection::Object(obj)) => obj.get(field).cloned(),
+        _ => None,
+    };
+    found.ok_or_else(|| {
+        ChimeraRuntimeFailure::VarWrongType(
+            response_value.error_print(context),
+            VarTypes::HttpResponse,
+            context.current_line,
+        )
+    })
+}
+
+/// Check a status code against an expected code, a class of codes like "2xx", or a list of either
+fn status_matches(
+    context: &Context,
+    expected_value: &Value,
+    expected: &DataKind,
+    status_code: u64,
+) -> Result<bool, ChimeraRuntimeFailure> {
+    let wrong_type = || {
+        ChimeraRuntimeFailure::VarWrongType(
+            expected_value.error_print(context),
+            VarTypes::StatusCode,
+            context.current_line,
+        )
+    };
+    match expected {
+        DataKind::Collection(Collection::List(codes)) => {
+            for code in codes {
+                match code.borrow()?.deref() {
+                    DataKind::Collection(_) => return Err(wrong_type()),
+                    literal => {
+                        if status_matches(context, expected_value, literal, status_code)? {
+                            return Ok(true);
+                        }
+                    }
+                }
+            }
+            Ok(false)
+        }
+        DataKind::Literal(Literal::String(class)) => {
+            let class = class.to_ascii_lowercase();
+            match class
+                .strip_suffix("xx")
+                .and_then(|digit| digit.parse::<u64>().ok())
+            {
+                Some(digit) if digit < 10 && class.len() == 3 => Ok(status_code / 100 == digit),
+                _ => Err(wrong_type()),
+            }
+        }
+        // A code can be any whole number which is not negative, like 201 or 201.0
+        DataKind::Literal(Literal::Number(number)) => {
+            let code = match *number {
+                NumberKind::U64(unsigned) => Some(unsigned),
+                NumberKind::I64(signed) => u64::try_from(signed).ok(),
+                NumberKind::F64(float)
+                    if float.is_finite() && float.fract() == 0.0 && float >= 0.0 =>
+                {
+                    Some(float as u64)
+                }
+                NumberKind::F64(_) => None,
+            };
+            Ok(code.ok_or_else(wrong_type)? == status_code)
+        }
+        _ => Err(wrong_type()),
+    }
+}
+
+/// Resolve the paths ignored by a SNAPSHOT assertion, where each value is a path or a list of paths
+fn resolve_ignore_paths(
+    context: &Context,
+    ignore: &[Value],
+) -> Result<Vec<String>, ChimeraRuntimeFailure> {
+    let mut paths: Vec<String> = Vec::new();
+    for value in ignore {
+        let resolved = value.resolve(context)?;
+        let borrowed = resolved.borrow()?;
+        match borrowed.deref() {
+            DataKind::Collection(Collection::List(list)) => {
+                for member in list {
+                    let path = member
+                        .borrow()?
+                        .try_into_string(value.error_print(context), context)?
+                        .to_owned();
+                    paths.push(path);
+                }
+            }
+            other => paths.push(
+                other
+                    .try_into_string(value.error_print(context), context)?
+                    .to_owned(),
+            ),
+        }
+    }
+    Ok(paths)
+}
+
+/// Describe what a value in a failed assertion resolved to, on its own indented lines
+fn describe_resolved(name: String, data: &DataKind) -> Result<String, ChimeraRuntimeFailure> {
+    let printed = pretty_print(data)?;
+    if !printed.contains('\n') {
+        return Ok(format!("\n  {} was {}", name, printed));
+    }
+    let mut described = format!("\n  {} was:", name);
+    for line in printed.lines() {
+        described.push_str(&format!("\n    {}", line));
+    }
+    Ok(described)
+}
+
+/// Validate data against the JSON Schema file at `schema_path`, relative to the test file, and
+/// list every violation by the JSON pointer to the value which caused it
+fn schema_violations(
+    context: &Context,
+    schema_path: &str,
+    data: &DataKind,
+) -> Result<Vec<String>, ChimeraRuntimeFailure> {
+    let full_path = context.get_test_directory().join(schema_path);
+    let schema_error =
+        |reason: String| ChimeraRuntimeFailure::SchemaError(reason, context.current_line);
+    let schema_contents = fs::read_to_string(&full_path).map_err(|_| {
+        schema_error(format!(
+            "Failed to read schema file '{}'",
+            full_path.display()
+        ))
+    })?;
+    let schema: serde_json::Value = serde_json::from_str(&schema_contents).map_err(|e| {
+        schema_error(format!(
+            "Schema file '{}' is not valid JSON, {}",
+            schema_path, e
+        ))
+    })?;
+    let validator = jsonschema::draft202012::new(&schema).map_err(|e| {
+        schema_error(format!(
+            "Schema file '{}' is not a valid schema, {}",
+            schema_path, e
+        ))
+    })?;
+    let instance = serde_json::to_value(data).map_err(|_| {
+        ChimeraRuntimeFailure::InternalError("converting data into JSON".to_owned())
+    })?;
+    let violations = validator
+        .iter_errors(&instance)
+        .map(|violation| {
+            let pointer = violation.instance_path.to_string();
+            let pointer = match pointer.is_empty() {
+                true => "/".to_owned(),
+                false => pointer,
+            };
+            format!("{}: {}", pointer, violation)
+        })
+        .collect();
+    Ok(violations)
+}
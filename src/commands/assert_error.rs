@@ -0,0 +1,64 @@
+use crate::abstract_syntax_tree::AssertErrorBlock;
+use crate::err_handle::ChimeraRuntimeFailure;
+use crate::frontend::{run_statement, Context, StatementOutcome};
+use std::io::Write;
+
+pub fn assert_error_command<W: Write>(
+    context: &mut Context,
+    writer: &mut W,
+    assert_error_block: &AssertErrorBlock,
+    depth: usize,
+) -> Result<StatementOutcome, ChimeraRuntimeFailure> {
+    let line = context.current_line;
+    let raised = match run_block(context, writer, assert_error_block, depth) {
+        Ok(StatementOutcome::Continue) => {
+            return Err(ChimeraRuntimeFailure::TestFailure(
+                format!(
+                    "Expected the block to raise a {} error, but it finished without one",
+                    assert_error_block.variant
+                ),
+                line,
+            ))
+        }
+        // A SKIP or a timeout ends the test rather than being an error the block can expect
+        Ok(skip @ StatementOutcome::Skip(_)) => return Ok(skip),
+        Err(timeout @ ChimeraRuntimeFailure::Timeout(_, _)) => return Err(timeout),
+        Err(raised) => raised,
+    };
+    if raised.get_variant_name() == assert_error_block.variant {
+        context.log_verbose(format!(
+            "ASSERT ERROR - got the expected {} error: {}",
+            assert_error_block.variant, raised
+        ));
+        return Ok(StatementOutcome::Continue);
+    }
+    Err(ChimeraRuntimeFailure::TestFailure(
+        format!(
+            "Expected the block to raise a {} error, but it raised a {} error\n  {}",
+            assert_error_block.variant,
+            raised.get_variant_name(),
+            raised
+        ),
+        line,
+    ))
+}
+
+// Runs the block, returning the first error raised by any of its statements or a SKIP which ended it
+fn run_block<W: Write>(
+    context: &mut Context,
+    writer: &mut W,
+    assert_error_block: &AssertErrorBlock,
+    depth: usize,
+) -> Result<StatementOutcome, ChimeraRuntimeFailure> {
+    // A failed EXPECT inside the block counts as a raised TestFailure
+    for statement in &assert_error_block.statements {
+        let statement_result = run_statement(context, writer, statement, depth);
+        if let Some(soft_failure) = context.take_soft_failures().into_iter().next() {
+            return Err(soft_failure);
+        }
+        if let StatementOutcome::Skip(reason) = statement_result? {
+            return Ok(StatementOutcome::Skip(reason));
+        }
+    }
+    Ok(StatementOutcome::Continue)
+}
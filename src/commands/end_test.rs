@@ -0,0 +1,25 @@
+use crate::abstract_syntax_tree::Value;
+use crate::err_handle::ChimeraRuntimeFailure;
+use crate::frontend::{Context, StatementOutcome};
+
+/// Ends the test with a SKIP status, so no other statements run
+pub fn skip_command(
+    context: &Context,
+    reason: &Value,
+) -> Result<StatementOutcome, ChimeraRuntimeFailure> {
+    Ok(StatementOutcome::Skip(resolve_reason(context, reason)?))
+}
+
+/// Ends the test with a failure
+pub fn fail_command(context: &Context, reason: &Value) -> Result<(), ChimeraRuntimeFailure> {
+    Err(ChimeraRuntimeFailure::TestFailure(
+        resolve_reason(context, reason)?,
+        context.current_line,
+    ))
+}
+
+fn resolve_reason(context: &Context, reason: &Value) -> Result<String, ChimeraRuntimeFailure> {
+    let resolved = reason.resolve(context)?;
+    let reason = resolved.borrow()?.to_string();
+    Ok(reason)
+}
@@ -0,0 +1,138 @@
+use crate::abstract_syntax_tree::{EventuallyBlock, KeyValuePair};
+use crate::err_handle::ChimeraRuntimeFailure;
+use crate::frontend::{run_statement, Context, StatementOutcome};
+use std::io::Write;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long an EVENTUALLY block keeps retrying and how long it waits between attempts
+struct EventuallyOptions {
+    timeout: Duration,
+    interval: Duration,
+    attempts: Option<u64>,
+}
+
+impl Default for EventuallyOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(10),
+            interval: Duration::from_millis(500),
+            attempts: None,
+        }
+    }
+}
+
+pub fn eventually_command<W: Write>(
+    context: &mut Context,
+    writer: &mut W,
+    eventually_block: &EventuallyBlock,
+    depth: usize,
+) -> Result<StatementOutcome, ChimeraRuntimeFailure> {
+    let options = resolve_options(context, &eventually_block.key_val_pairs)?;
+    let start = Instant::now();
+    let mut attempt: u64 = 0;
+    loop {
+        attempt += 1;
+        let (failure, soft) = match run_block(context, writer, eventually_block, depth) {
+            Ok(outcome) => return Ok(outcome),
+            // A timeout ends the test rather than failing the attempt
+            Err((timeout @ ChimeraRuntimeFailure::Timeout(_, _), _)) => return Err(timeout),
+            Err(failed) => failed,
+        };
+        let out_of_attempts = options.attempts.is_some_and(|max| attempt >= max);
+        let out_of_time = start.elapsed() + options.interval > options.timeout;
+        if out_of_attempts || out_of_time {
+            // Report the last failure, noting how long the block was given to pass
+            let failure = match failure {
+                ChimeraRuntimeFailure::TestFailure(msg, line) => ChimeraRuntimeFailure::TestFailure(
+                    format!(
+                        "EVENTUALLY block did not pass after {} attempts in {:.1}s, last failure: {}",
+                        attempt,
+                        start.elapsed().as_secs_f64(),
+                        msg
+                    ),
+                    line,
+                ),
+                other => other,
+            };
+            // A block which gave up because of an EXPECT lets the test keep running
+            if soft {
+                context.record_soft_failure(failure);
+                return Ok(StatementOutcome::Continue);
+            }
+            return Err(failure);
+        }
+        context.log_verbose(format!(
+            "EVENTUALLY - attempt {} failed, retrying in {}s - {}",
+            attempt,
+            options.interval.as_secs_f64(),
+            failure
+        ));
+        thread::sleep(context.cap_to_deadline(options.interval));
+    }
+}
+
+// Runs the block once, returning the failure which stopped it and whether it came from an EXPECT. A
+// SKIP ends the block and the test
+fn run_block<W: Write>(
+    context: &mut Context,
+    writer: &mut W,
+    eventually_block: &EventuallyBlock,
+    depth: usize,
+) -> Result<StatementOutcome, (ChimeraRuntimeFailure, bool)> {
+    // A failed EXPECT fails the attempt the same as an ASSERT, so the block is retried
+    for statement in &eventually_block.statements {
+        let statement_result = run_statement(context, writer, statement, depth);
+        if let Some(soft_failure) = context.take_soft_failures().into_iter().next() {
+            return Err((soft_failure, true));
+        }
+        if let StatementOutcome::Skip(reason) =
+            statement_result.map_err(|failure| (failure, false))?
+        {
+            return Ok(StatementOutcome::Skip(reason));
+        }
+    }
+    Ok(StatementOutcome::Continue)
+}
+
+fn resolve_options(
+    context: &Context,
+    key_val_pairs: &[KeyValuePair],
+) -> Result<EventuallyOptions, ChimeraRuntimeFailure> {
+    let mut options = EventuallyOptions::default();
+    for pair in key_val_pairs {
+        let data = pair.value.resolve(context)?;
+        let borrowed = data.borrow()?;
+        match pair.key.as_str() {
+            "timeout" | "interval" => {
+                let seconds = borrowed
+                    .try_into_number_kind(&pair.value, context)?
+                    .to_f64();
+                let duration = match Duration::try_from_secs_f64(seconds) {
+                    Ok(duration) => duration,
+                    Err(_) => {
+                        return Err(ChimeraRuntimeFailure::InvalidDuration(
+                            format!("{}=>{}", pair.key, seconds),
+                            context.current_line,
+                        ))
+                    }
+                };
+                if pair.key == "timeout" {
+                    options.timeout = duration;
+                } else {
+                    options.interval = duration;
+                }
+            }
+            "attempts" => {
+                options.attempts = Some(borrowed.try_into_u64(&pair.value, context)?);
+            }
+            _ => {
+                return Err(ChimeraRuntimeFailure::UnknownOption(
+                    pair.key.clone(),
+                    context.current_line,
+                ))
+            }
+        }
+    }
+    Ok(options)
+}
@@ -9,10 +9,10 @@ use std::ops::{Deref, DerefMut};
 
 pub fn expression_command(
     context: &Context,
-    expression: Expression,
+    expression: &Expression,
 ) -> Result<Data, ChimeraRuntimeFailure> {
     match expression {
-        Expression::Literal(literal) => Ok(Data::from_literal(literal)),
+        Expression::Literal(literal) => Ok(Data::from_literal(literal.clone())),
         Expression::HttpCommand(http_command) => {
             let client = CLIENT
                 .get()
@@ -31,8 +31,8 @@ pub fn expression_command(
                     Ok(Data::from_vec(list))
                 }
                 ListExpression::ListArgument(list_command) => {
-                    match list_command.operation {
-                        ListCommandOperations::MutateOperations(ref mutable_operation) => {
+                    match &list_command.operation {
+                        ListCommandOperations::MutateOperations(mutable_operation) => {
                             let variable_map = context.get_var_map();
                             match variable_map
                                 .get_mut(context, list_command.list_name.as_str())?
@@ -98,6 +98,34 @@ pub fn expression_command(
                 }
             }
         }
+        Expression::TypeOf(value) => {
+            let resolved = value.resolve(context)?;
+            let type_name = resolved.borrow()?.type_name();
+            Ok(Data::from_literal(Literal::String(type_name.to_owned())))
+        }
+        Expression::Match(value, pattern) => {
+            let regex = pattern.resolve_regex(context)?;
+            let resolved = value.resolve(context)?;
+            let binding = resolved.borrow()?;
+            let haystack = binding.try_into_string(value.error_print(context), context)?;
+            // A match resolves to a list of the whole match followed by each capture group, where
+            // a group which did not participate in the match is null. No match resolves to null
+            match regex.captures(haystack) {
+                Some(captures) => {
+                    let groups: Vec<Data> = captures
+                        .iter()
+                        .map(|group| match group {
+                            Some(matched) => {
+                                Data::from_literal(Literal::String(matched.as_str().to_owned()))
+                            }
+                            None => Data::from_literal(Literal::Null),
+                        })
+                        .collect();
+                    Ok(Data::from_vec(groups))
+                }
+                None => Ok(Data::from_literal(Literal::Null)),
+            }
+        }
         Expression::FormattedString(formatted_string) => {
             let mut built_str = String::new();
             for value in formatted_string {
@@ -115,7 +143,7 @@ pub fn expression_command(
                         let binding = resolved.borrow()?;
                         built_str.push_str(binding.to_string().as_str());
                     }
-                    Value::FormattedString(_) => {
+                    Value::FormattedString(_) | Value::List(_) => {
                         return Err(ChimeraRuntimeFailure::InternalError(
                             "resolving a recursive formatted string".to_owned(),
                         ))
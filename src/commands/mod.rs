@@ -1,4 +1,8 @@
 pub mod assert;
+pub mod assert_error;
 pub mod assignment;
+pub mod end_test;
+pub mod eventually;
 pub mod expression;
 pub mod print;
+pub mod sleep;
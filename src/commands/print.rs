@@ -8,7 +8,7 @@ use std::ops::Deref;
 pub fn print_command<W: Write>(
     context: &Context,
     writer: &mut W,
-    print_cmd: Value,
+    print_cmd: &Value,
     depth: usize,
 ) -> Result<(), ChimeraRuntimeFailure> {
     let resolved = print_cmd.resolve(context)?;
@@ -0,0 +1,32 @@
+use crate::abstract_syntax_tree::Value;
+use crate::err_handle::ChimeraRuntimeFailure;
+use crate::frontend::Context;
+use std::thread;
+use std::time::Duration;
+
+pub fn sleep_command(context: &Context, seconds: &Value) -> Result<(), ChimeraRuntimeFailure> {
+    let resolved = seconds.resolve(context)?;
+    let as_seconds = resolved
+        .borrow()?
+        .try_into_number_kind(seconds, context)?
+        .to_f64();
+    let duration = match Duration::try_from_secs_f64(as_seconds) {
+        Ok(duration) => duration,
+        Err(_) => {
+            return Err(ChimeraRuntimeFailure::InvalidDuration(
+                seconds.error_print(context),
+                context.current_line,
+            ))
+        }
+    };
+    // Sleeps can be scaled down, or disabled with a scale of 0, to make local runs faster
+    let scale = crate::SLEEP_SCALE.get().copied().unwrap_or(1.0);
+    // A sleep longer than the time left before the test's deadline only sleeps until it
+    let scaled = context.cap_to_deadline(duration.mul_f64(scale));
+    if scaled.is_zero() {
+        return Ok(());
+    }
+    context.log_verbose(format!("SLEEPING - {}s", scaled.as_secs_f64()));
+    thread::sleep(scaled);
+    Ok(())
+}
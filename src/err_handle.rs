@@ -56,6 +56,8 @@ pub enum VarTypes {
     List,
     Containable,
     Literal,
+    StatusCode,
+    Tolerance,
 }
 
 impl Display for VarTypes {
@@ -68,6 +70,11 @@ impl Display for VarTypes {
             VarTypes::List => write!(f, "List"),
             VarTypes::Containable => write!(f, "List or Object"),
             VarTypes::Literal => write!(f, "Literal (number, bool, string, or null)"),
+            VarTypes::StatusCode => write!(
+                f,
+                "Status code (a number, a class like \"2xx\", or a list of either)"
+            ),
+            VarTypes::Tolerance => write!(f, "Tolerance (a finite number, 0 or greater)"),
         }
     }
 }
@@ -84,6 +91,17 @@ pub enum ChimeraRuntimeFailure {
     OutOfBounds(i32),
     BorrowError(String),
     InvalidHeader(i32, String),
+    UnknownOption(String, i32),
+    InvalidDuration(String, i32),
+    InvalidRegex(String, i32),
+    SchemaError(String, i32),
+    UnknownType(String, i32),
+    SnapshotError(String, i32),
+    ParamsError(String, i32),
+    /// A test ran past the budget in seconds set by its `[timeout=N]` decorator
+    Timeout(f64, i32),
+    /// A `[fixture]`, `[before-all]`, `[before-each]`, or `[after-each]` case a test depends on did not pass
+    HookFailure(String),
 }
 
 impl Display for ChimeraRuntimeFailure {
@@ -147,6 +165,43 @@ impl Display for ChimeraRuntimeFailure {
                 "ERROR on line {}: Header '{}' is not valid",
                 line, header
             ),
+            ChimeraRuntimeFailure::UnknownOption(option, line) => write!(
+                f,
+                "ERROR on line {}: '{}' is not a valid option",
+                line, option
+            ),
+            ChimeraRuntimeFailure::InvalidDuration(duration, line) => write!(
+                f,
+                "ERROR on line {}: {} is not a valid number of seconds",
+                line, duration
+            ),
+            ChimeraRuntimeFailure::InvalidRegex(pattern, line) => write!(
+                f,
+                "ERROR on line {}: '{}' is not a valid regular expression",
+                line, pattern
+            ),
+            ChimeraRuntimeFailure::SchemaError(reason, line) => {
+                write!(f, "ERROR on line {}: {}", line, reason)
+            }
+            ChimeraRuntimeFailure::UnknownType(type_name, line) => write!(
+                f,
+                "ERROR on line {}: '{}' is not a valid type, expected one of {}",
+                line,
+                type_name,
+                crate::literal::TYPE_NAMES.join(", ")
+            ),
+            ChimeraRuntimeFailure::SnapshotError(reason, line) => {
+                write!(f, "ERROR on line {}: {}", line, reason)
+            }
+            ChimeraRuntimeFailure::ParamsError(reason, line) => {
+                write!(f, "ERROR on line {}: {}", line, reason)
+            }
+            ChimeraRuntimeFailure::Timeout(budget, line) => write!(
+                f,
+                "ERROR on line {}: The test did not finish within its timeout of {}s",
+                line, budget
+            ),
+            ChimeraRuntimeFailure::HookFailure(reason) => write!(f, "ERROR: {}", reason),
         }
     }
 }
@@ -184,33 +239,115 @@ impl PartialEq for ChimeraRuntimeFailure {
             ChimeraRuntimeFailure::InvalidHeader(_, _) => {
                 matches!(other, ChimeraRuntimeFailure::InvalidHeader(_, _))
             }
+            ChimeraRuntimeFailure::UnknownOption(_, _) => {
+                matches!(other, ChimeraRuntimeFailure::UnknownOption(_, _))
+            }
+            ChimeraRuntimeFailure::InvalidDuration(_, _) => {
+                matches!(other, ChimeraRuntimeFailure::InvalidDuration(_, _))
+            }
+            ChimeraRuntimeFailure::InvalidRegex(_, _) => {
+                matches!(other, ChimeraRuntimeFailure::InvalidRegex(_, _))
+            }
+            ChimeraRuntimeFailure::SchemaError(_, _) => {
+                matches!(other, ChimeraRuntimeFailure::SchemaError(_, _))
+            }
+            ChimeraRuntimeFailure::UnknownType(_, _) => {
+                matches!(other, ChimeraRuntimeFailure::UnknownType(_, _))
+            }
+            ChimeraRuntimeFailure::SnapshotError(_, _) => {
+                matches!(other, ChimeraRuntimeFailure::SnapshotError(_, _))
+            }
+            ChimeraRuntimeFailure::ParamsError(_, _) => {
+                matches!(other, ChimeraRuntimeFailure::ParamsError(_, _))
+            }
+            ChimeraRuntimeFailure::Timeout(_, _) => {
+                matches!(other, ChimeraRuntimeFailure::Timeout(_, _))
+            }
+            ChimeraRuntimeFailure::HookFailure(_) => {
+                matches!(other, ChimeraRuntimeFailure::HookFailure(_))
+            }
         }
     }
 }
 
 impl ChimeraRuntimeFailure {
     pub fn print_error<W: Write>(&self, writer: &mut W, padding: usize) {
-        writeln!(writer, "{:indent$}{}", "", self, indent = padding + 1)
-            .expect("Failed to write an error");
+        // Pad every line so multi-line messages stay aligned with the test they belong to
+        for line in self.to_string().lines() {
+            writeln!(writer, "{:indent$}{}", "", line, indent = padding + 1)
+                .expect("Failed to write an error");
+        }
     }
+}
 
-    #[allow(dead_code)] // Used by tests
-    pub fn get_variant_name(&self) -> &str {
-        match self {
-            ChimeraRuntimeFailure::VarNotFound(_, _) => "VarNotFound",
-            ChimeraRuntimeFailure::VarWrongType(_, _, _) => "VarWrongType",
-            ChimeraRuntimeFailure::TestFailure(_, _) => "TestFailure",
-            ChimeraRuntimeFailure::InternalError(_) => "InternalError",
-            ChimeraRuntimeFailure::WebRequestFailure(_, _) => "WebRequestFailure",
-            ChimeraRuntimeFailure::BadSubfieldAccess(_, _, _) => "BadSubfieldAccess",
-            ChimeraRuntimeFailure::TriedToIndexWithNonNumber(_) => "TriedToIndexWithNonNumber",
-            ChimeraRuntimeFailure::OutOfBounds(_) => "OutOfBounds",
-            ChimeraRuntimeFailure::BorrowError(_) => "BorrowError",
-            ChimeraRuntimeFailure::InvalidHeader(_, _) => "InvalidHeader",
+/// Defines `ChimeraRuntimeFailure::get_variant_name` along with `VARIANT_NAMES` from one list of
+/// variants, so the two cannot drift apart. The match is exhaustive, so every variant must be listed
+/// as either expectable or unexpectable
+macro_rules! variant_names {
+    (expectable: [$($expectable:ident),* $(,)?], unexpectable: [$($unexpectable:ident),* $(,)?] $(,)?) => {
+        impl ChimeraRuntimeFailure {
+            /// The name of every variant an ASSERT ERROR block can expect, as returned by
+            /// `get_variant_name`
+            pub const VARIANT_NAMES: &'static [&'static str] = &[$(stringify!($expectable)),*];
+
+            pub fn get_variant_name(&self) -> &str {
+                match self {
+                    $(ChimeraRuntimeFailure::$expectable(..) => stringify!($expectable),)*
+                    $(ChimeraRuntimeFailure::$unexpectable(..) => stringify!($unexpectable),)*
+                }
+            }
         }
-    }
+    };
+}
+
+// A timeout always ends the test, and a hook failure is never raised by a statement, so neither
+// can be expected
+variant_names! {
+    expectable: [
+        VarNotFound,
+        VarWrongType,
+        TestFailure,
+        InternalError,
+        WebRequestFailure,
+        BadSubfieldAccess,
+        TriedToIndexWithNonNumber,
+        OutOfBounds,
+        BorrowError,
+        InvalidHeader,
+        UnknownOption,
+        InvalidDuration,
+        InvalidRegex,
+        SchemaError,
+        UnknownType,
+        SnapshotError,
+        ParamsError,
+    ],
+    unexpectable: [Timeout, HookFailure],
 }
 
 pub fn print_error<W: Write>(writer: &mut W, err_msg: &str) {
     writeln!(writer, "ERROR: {}", err_msg).expect("Failed to write an error");
 }
+
+#[cfg(test)]
+mod err_handle_tests {
+    use super::*;
+
+    #[test]
+    /// Test that the names ASSERT ERROR can expect are the ones raised errors report
+    fn variant_names() {
+        let params_error = ChimeraRuntimeFailure::ParamsError("".to_owned(), 0);
+        assert_eq!(params_error.get_variant_name(), "ParamsError");
+        assert!(ChimeraRuntimeFailure::VARIANT_NAMES.contains(&params_error.get_variant_name()));
+        for unexpectable in [
+            ChimeraRuntimeFailure::Timeout(1.0, 0),
+            ChimeraRuntimeFailure::HookFailure("".to_owned()),
+        ] {
+            assert!(
+                !ChimeraRuntimeFailure::VARIANT_NAMES.contains(&unexpectable.get_variant_name()),
+                "{} should not be expectable",
+                unexpectable.get_variant_name()
+            );
+        }
+    }
+}
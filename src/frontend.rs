@@ -1,28 +1,111 @@
-use crate::abstract_syntax_tree::{BlockContents, ChimeraScriptAST, Function, Statement};
+use crate::abstract_syntax_tree::{
+    BlockContents, ChimeraScriptAST, Function, ParamsBlock, Statement, Teardown,
+};
 use crate::err_handle::{ChimeraCompileError, ChimeraRuntimeFailure};
 use crate::literal::Data;
+use crate::util::params::load_rows;
+use crate::util::tags::TagFilter;
 use crate::util::timer::Timer;
 use crate::variable_map::VariableMap;
 use pest::iterators::Pairs;
 use pest::Parser;
 use pest_derive::Parser;
-use std::ffi::OsStr;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::io::Write;
 use std::iter::Sum;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// When a test with a `[timeout=N]` decorator must finish by, which its nested cases share
+#[derive(Debug, Clone, Copy)]
+pub struct Deadline {
+    at: Instant,
+    budget: Duration,
+}
+
+impl Deadline {
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            at: Instant::now() + budget,
+            budget,
+        }
+    }
+    /// The earlier of two deadlines, as a nested case cannot outlive its parent
+    fn earliest(self, other: Option<Deadline>) -> Self {
+        match other {
+            Some(other) if other.at < self.at => other,
+            _ => self,
+        }
+    }
+}
 
 pub struct Context<'a> {
     pub current_line: i32,
     variable_map: &'a mut VariableMap,
+    // Directory of the file being run, files referenced by a test are resolved relative to it
+    test_directory: &'a Path,
+    // Messages which are only printed when running in verbose mode. This is a RefCell so things
+    // which only have a shared reference to the context, like a web client, can still log
+    verbose_log: RefCell<Vec<String>>,
+    // Failures from EXPECT statements, which are recorded and let the test keep running
+    soft_failures: RefCell<Vec<ChimeraRuntimeFailure>>,
+    deadline: Option<Deadline>,
 }
 
 impl<'a> Context<'a> {
-    pub fn new(variable_map: &'a mut VariableMap) -> Context<'a> {
+    pub fn new(variable_map: &'a mut VariableMap, test_directory: &'a Path) -> Context<'a> {
         Self {
             current_line: 0,
             variable_map,
+            test_directory,
+            verbose_log: RefCell::new(Vec::new()),
+            soft_failures: RefCell::new(Vec::new()),
+            deadline: None,
         }
     }
+    pub fn set_deadline(&mut self, deadline: Option<Deadline>) {
+        self.deadline = deadline;
+    }
+    /// Fails with a Timeout once the test has run past its deadline
+    pub fn check_deadline(&self) -> Result<(), ChimeraRuntimeFailure> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline.at => Err(ChimeraRuntimeFailure::Timeout(
+                deadline.budget.as_secs_f64(),
+                self.current_line,
+            )),
+            _ => Ok(()),
+        }
+    }
+    /// How long something which blocks, like a request or a sleep, can take before the deadline
+    pub fn time_remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.at.saturating_duration_since(Instant::now()))
+    }
+    /// Shorten a wait so it ends by the deadline
+    pub fn cap_to_deadline(&self, duration: Duration) -> Duration {
+        match self.time_remaining() {
+            Some(remaining) => duration.min(remaining),
+            None => duration,
+        }
+    }
+    pub fn log_verbose(&self, message: String) {
+        self.verbose_log.borrow_mut().push(message);
+    }
+    pub fn take_verbose_log(&self) -> Vec<String> {
+        self.verbose_log.take()
+    }
+    pub fn record_soft_failure(&self, failure: ChimeraRuntimeFailure) {
+        self.soft_failures.borrow_mut().push(failure);
+    }
+    /// Take the soft failures recorded since they were last taken
+    pub fn take_soft_failures(&self) -> Vec<ChimeraRuntimeFailure> {
+        self.soft_failures.take()
+    }
+    pub fn get_test_directory(&self) -> &Path {
+        self.test_directory
+    }
     pub fn get_var_map(&self) -> &VariableMap {
         // Re-borrow as a shared immutable reference
         &*self.variable_map
@@ -35,13 +118,14 @@ impl<'a> Context<'a> {
 #[derive(Debug)]
 pub enum Status {
     Success,
-    Failure(ChimeraRuntimeFailure),
-    Error(ChimeraRuntimeFailure),
+    /// Every failure of a test, which can be several when EXPECT statements failed
+    Failure(Vec<ChimeraRuntimeFailure>),
+    /// The error which stopped a test, along with any EXPECT statements which failed before it
+    Error(ChimeraRuntimeFailure, Vec<ChimeraRuntimeFailure>),
     ExpectedFailure,
     UnexpectedSuccess,
-    // TODO: Remove me
-    #[allow(dead_code)]
-    Skip,
+    /// A test ended by a SKIP statement, with the reason it gave
+    Skip(String),
 }
 
 impl Display for Status {
@@ -49,10 +133,10 @@ impl Display for Status {
         match self {
             Status::Success => write!(f, "SUCCESS"),
             Status::Failure(_) => write!(f, "FAILURE"),
-            Status::Error(_) => write!(f, "ERROR"),
+            Status::Error(..) => write!(f, "ERROR"),
             Status::ExpectedFailure => write!(f, "EXPECTED FAILURE"),
             Status::UnexpectedSuccess => write!(f, "UNEXPECTED SUCCESS"),
-            Status::Skip => write!(f, "SKIP"),
+            Status::Skip(reason) => write!(f, "SKIP - {}", reason),
         }
     }
 }
@@ -62,20 +146,14 @@ pub struct ResultCount {
     success: usize,
     failure: usize,
     error: usize,
+    skip: usize,
+    flaky: usize,
     total_tests: usize,
 }
 
 impl Display for ResultCount {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Ran {} tests with {} successes, {} failures, and {} errors\n\n{}",
-            self.total_tests,
-            self.success,
-            self.failure,
-            self.error,
-            self.overall_result()
-        )
+        write!(f, "{}", self.summary(None))
     }
 }
 
@@ -85,15 +163,26 @@ impl ResultCount {
             success: 0,
             failure: 0,
             error: 0,
+            skip: 0,
+            flaky: 0,
             total_tests: 0,
         }
     }
-    pub fn new(input: (usize, usize, usize, usize)) -> Self {
+    pub fn new(input: (usize, usize, usize, usize, usize, usize)) -> Self {
         Self {
             success: input.0,
             failure: input.1,
             error: input.2,
-            total_tests: input.3,
+            skip: input.3,
+            flaky: input.4,
+            total_tests: input.5,
+        }
+    }
+    // Flaky tests are counted as successes, this notes how many of those successes were flaky
+    fn flaky_note(&self) -> String {
+        match self.flaky {
+            0 => String::new(),
+            flaky => format!(" ({} flaky)", flaky),
         }
     }
     pub fn overall_result(&self) -> &str {
@@ -104,17 +193,26 @@ impl ResultCount {
         }
     }
     pub fn print_with_time<W: Write>(&self, writer: &mut W, time_taken: &str) {
-        writeln!(
-            writer,
-            "Ran {} tests in {} with {} successes, {} failures, and {} errors\n\n{}",
+        writeln!(writer, "{}", self.summary(Some(time_taken)))
+            .expect("Failed to write during a print");
+    }
+    // The counts and overall result, along with how long the tests took to run when it is known
+    fn summary(&self, time_taken: Option<&str>) -> String {
+        let time_taken = match time_taken {
+            Some(time_taken) => format!(" in {}", time_taken),
+            None => String::new(),
+        };
+        format!(
+            "Ran {} tests{} with {} successes{}, {} failures, {} errors, and {} skipped\n\n{}",
             self.total_tests,
             time_taken,
             self.success,
+            self.flaky_note(),
             self.failure,
             self.error,
+            self.skip,
             self.overall_result()
         )
-        .expect("Failed to write during a print");
     }
 
     // This is used by a test
@@ -123,6 +221,26 @@ impl ResultCount {
         self.success
     }
 
+    #[allow(dead_code)] // Used by tests
+    pub fn failure_count(&self) -> usize {
+        self.failure
+    }
+
+    #[allow(dead_code)] // Used by tests
+    pub fn error_count(&self) -> usize {
+        self.error
+    }
+
+    #[allow(dead_code)] // Used by tests
+    pub fn skip_count(&self) -> usize {
+        self.skip
+    }
+
+    #[allow(dead_code)] // Used by tests
+    pub fn flaky_count(&self) -> usize {
+        self.flaky
+    }
+
     #[allow(dead_code)]
     pub fn from_test_results(results: Vec<TestResult>) -> Self {
         results.iter().map(|x| x.get_result_counts()).sum()
@@ -136,6 +254,8 @@ impl std::ops::Add for ResultCount {
             success: self.success + rhs.success,
             failure: self.failure + rhs.failure,
             error: self.error + rhs.error,
+            skip: self.skip + rhs.skip,
+            flaky: self.flaky + rhs.flaky,
             total_tests: self.total_tests + rhs.total_tests,
         }
     }
@@ -154,6 +274,8 @@ pub struct TestResult {
     name: String,
     status: Status,
     pub subtest_results: Vec<TestResult>,
+    /// How many times the test was run, more than once when a [retry=N] test failed
+    attempts: u32,
 }
 
 impl TestResult {
@@ -162,16 +284,26 @@ impl TestResult {
             name,
             status,
             subtest_results,
+            attempts: 1,
         }
     }
+    pub fn with_attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts;
+        self
+    }
+    /// A test which passed, but only after failing at least once
+    pub fn is_flaky(&self) -> bool {
+        self.attempts > 1 && self.passed()
+    }
     pub fn get_result_counts(&self) -> ResultCount {
+        let flaky = usize::from(self.is_flaky());
         let res = ResultCount::new(match self.status {
-            Status::Success => (1, 0, 0, 1),
-            Status::Failure(_) => (0, 1, 0, 1),
-            Status::Error(_) => (0, 0, 1, 1),
-            Status::ExpectedFailure => (1, 0, 0, 1),
-            Status::UnexpectedSuccess => (1, 0, 0, 1),
-            Status::Skip => (0, 0, 0, 0),
+            Status::Success => (1, 0, 0, 0, flaky, 1),
+            Status::Failure(_) => (0, 1, 0, 0, 0, 1),
+            Status::Error(..) => (0, 0, 1, 0, 0, 1),
+            Status::ExpectedFailure => (1, 0, 0, 0, flaky, 1),
+            Status::UnexpectedSuccess => (1, 0, 0, 0, flaky, 1),
+            Status::Skip(_) => (0, 0, 0, 1, 0, 1),
         });
         res + self
             .subtest_results
@@ -180,7 +312,6 @@ impl TestResult {
             .sum()
     }
 
-    #[allow(dead_code)] // Used by tests
     pub fn passed(&self) -> bool {
         matches!(
             self.status,
@@ -188,15 +319,42 @@ impl TestResult {
         )
     }
 
+    /// Whether the test passed along with every case nested in it, though a nested case may be skipped
+    fn passed_with_nested_cases(&self) -> bool {
+        self.passed()
+            && self.subtest_results.iter().all(|subtest| {
+                matches!(subtest.status, Status::Skip(_)) || subtest.passed_with_nested_cases()
+            })
+    }
+
     #[allow(dead_code)] // Used by tests
     pub fn error_kind(&self) -> Option<&ChimeraRuntimeFailure> {
         match &self.status {
-            Status::Error(e) => Some(e),
-            Status::Failure(f) => Some(f),
+            Status::Error(e, _) => Some(e),
+            Status::Failure(failures) => failures.first(),
             _ => None,
         }
     }
 
+    #[allow(dead_code)] // Used by tests
+    pub fn failures(&self) -> &[ChimeraRuntimeFailure] {
+        match &self.status {
+            Status::Failure(failures) => failures,
+            Status::Error(_, soft_failures) => soft_failures,
+            _ => &[],
+        }
+    }
+
+    #[allow(dead_code)] // Used by tests
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    #[allow(dead_code)] // Used by tests
+    pub fn status(&self) -> &Status {
+        &self.status
+    }
+
     #[allow(dead_code)] // Used by tests
     pub fn test_name(&self) -> &str {
         self.name.as_str()
@@ -208,74 +366,579 @@ impl TestResult {
 pub struct CScriptTokenPairs;
 
 /// Parse a string with Pest using the Main rule
-pub fn parse_main(input: &str) -> Result<Pairs<Rule>, ChimeraCompileError> {
+pub fn parse_main(input: &str) -> Result<Pairs<'_, Rule>, ChimeraCompileError> {
     match CScriptTokenPairs::parse(Rule::Main, input) {
         Ok(parsed) => Ok(parsed),
         Err(e) => Err(handle_ast_err(e)),
     }
 }
 
+/// Run the tests of a file selected by their tags, or every test when there is no tag filter
 pub fn run_functions<S: Write, E: Write>(
     writer: &mut S,
     err_writer: &mut E,
     ast: ChimeraScriptAST,
-    filename: &OsStr,
+    path: &Path,
+    fixtures: &mut SuiteFixtures,
+    tag_filter: Option<&TagFilter>,
+) -> Vec<TestResult> {
+    let selection = FileSelection::new(ast, tag_filter, None);
+    run_file(writer, err_writer, selection, path, fixtures)
+}
+
+/// Run the test of a file with the given name, if it is also selected by its tags
+pub fn run_function_by_name<S: Write, E: Write>(
+    writer: &mut S,
+    err_writer: &mut E,
+    ast: ChimeraScriptAST,
+    path: &Path,
+    fixtures: &mut SuiteFixtures,
+    tag_filter: Option<&TagFilter>,
+    function_name: &str,
+) -> Vec<TestResult> {
+    let selection = FileSelection::new(ast, tag_filter, Some(function_name));
+    run_file(writer, err_writer, selection, path, fixtures)
+}
+
+/// The tests of a file chosen to run by name or by tags, before any of them run. A selected test also
+/// runs the tests it depends on, even when they were not selected themselves
+pub struct FileSelection {
+    ast: ChimeraScriptAST,
+    // The names of the tests to run
+    selected: Vec<String>,
+    // The names of the tests which are only depended on and have an excluded tag, which are skipped
+    excluded: Vec<String>,
+}
+
+impl FileSelection {
+    pub fn new(
+        ast: ChimeraScriptAST,
+        tag_filter: Option<&TagFilter>,
+        function_name: Option<&str>,
+    ) -> Self {
+        let ChimeraScriptAST { functions, params } = ast;
+        let selected_by_tags = |function: &Function| match tag_filter {
+            Some(tag_filter) => tag_filter.selects(function),
+            None => true,
+        };
+        let mut selected: Vec<String> = functions
+            .iter()
+            .filter(|function| {
+                is_test(function)
+                    && selected_by_tags(function)
+                    && function_name.is_none_or(|name| function.has_name(name))
+            })
+            .map(|function| function.name.clone())
+            .collect();
+        let mut excluded: Vec<String> = Vec::new();
+        let mut index = 0;
+        while index < selected.len() {
+            // The dependencies of an excluded test are not needed, as it does not run
+            if excluded.contains(&selected[index]) {
+                index += 1;
+                continue;
+            }
+            let dependencies: Vec<String> = functions
+                .iter()
+                .filter(|function| is_test(function) && function.has_name(&selected[index]))
+                .flat_map(|function| function.dependencies())
+                .map(|dependency| dependency.to_owned())
+                .collect();
+            for dependency in dependencies {
+                if selected.contains(&dependency) {
+                    continue;
+                }
+                // A dependency with an excluded tag is not run, so neither are the tests which need it
+                let is_excluded = tag_filter.is_some_and(|tag_filter| {
+                    functions.iter().any(|function| {
+                        is_test(function)
+                            && function.has_name(&dependency)
+                            && tag_filter.excludes(function)
+                    })
+                });
+                if is_excluded {
+                    excluded.push(dependency.clone());
+                }
+                selected.push(dependency);
+            }
+            index += 1;
+        }
+        // Only the nested cases of a test selected by its tags are filtered, a test which only runs as a
+        // dependency runs all of them
+        let functions: Vec<Function> = functions
+            .into_iter()
+            .map(|function| match tag_filter {
+                Some(tag_filter) if selected_by_tags(&function) => tag_filter.prune(function),
+                _ => function,
+            })
+            .collect();
+        Self {
+            ast: ChimeraScriptAST { functions, params },
+            selected,
+            excluded,
+        }
+    }
+}
+
+// A test function which is not a hook
+fn is_test(function: &Function) -> bool {
+    function.hook().is_none() && function.is_test_function()
+}
+
+/// What the `[fixture]` cases of a suite set up. Every file of the suite starts with its own copy of
+/// the variables they set, so no file can change what another one sees. The fixtures are only run
+/// once a file has a test to run, so a suite where nothing is selected never sets them up
+pub struct SuiteFixtures<'f> {
+    // The suite's fixtures file, when it has one
+    file: Option<(&'f ChimeraScriptAST, &'f Path)>,
+    variables: VariableMap,
+    // Why the fixtures could not be set up, if one of them did not pass
+    failure: Option<String>,
+    // Run once every file of the suite has finished, if the fixtures were set up
+    teardown_stack: Vec<&'f Teardown>,
+    set_up: bool,
+}
+
+impl<'f> SuiteFixtures<'f> {
+    pub fn new() -> Self {
+        Self {
+            file: None,
+            variables: VariableMap::new(),
+            failure: None,
+            teardown_stack: Vec::new(),
+            set_up: false,
+        }
+    }
+    /// Fixtures which are set up from the `[fixture]` cases of the given fixtures file
+    pub fn from_file(ast: &'f ChimeraScriptAST, path: &'f Path) -> Self {
+        Self {
+            file: Some((ast, path)),
+            ..Self::new()
+        }
+    }
+    /// Run the fixtures if they have not been run yet
+    fn set_up<S: Write, E: Write>(&mut self, writer: &mut S, err_writer: &mut E) {
+        if self.set_up {
+            return;
+        }
+        self.set_up = true;
+        if let Some((ast, path)) = self.file {
+            run_fixtures(writer, err_writer, ast, path, self);
+        }
+    }
+}
+
+/// Run the `[fixture]` cases of a suite's fixtures file in order, stopping at the first which does
+/// not pass. Their teardown is not run, but pushed onto the fixtures' teardown stack to run once every
+/// file of the suite has finished
+fn run_fixtures<'f, S: Write, E: Write>(
+    writer: &mut S,
+    err_writer: &mut E,
+    ast: &'f ChimeraScriptAST,
+    path: &Path,
+    fixtures: &mut SuiteFixtures<'f>,
+) {
+    let test_directory = path.parent().unwrap_or(Path::new("."));
+    for function in ast
+        .functions
+        .iter()
+        .filter(|function| function.has_key("fixture"))
+    {
+        print_in_function(writer, &format!("STARTING FIXTURE - {}", function.name), 0);
+        let timer = Timer::new();
+        let mut context = Context::new(&mut fixtures.variables, test_directory);
+        let outcome = run_block(
+            &mut context,
+            writer,
+            err_writer,
+            function,
+            &mut fixtures.teardown_stack,
+            0,
+        );
+        let (status, _) = outcome.finish(false);
+        print_in_function(
+            writer,
+            &format!(
+                "FINISHED FIXTURE - {} - {} - {}",
+                function.name,
+                timer.finish(),
+                &status
+            ),
+            0,
+        );
+        if matches!(status, Status::Failure(_) | Status::Error(..)) {
+            fixtures.failure = Some(format!(
+                "The [fixture] case {} did not pass: {}",
+                function.name, status
+            ));
+            break;
+        }
+    }
+}
+
+/// Run the teardown of a suite's fixtures, once every file of the suite has finished. Nothing depends
+/// on it, so a teardown which fails is reported as its own result
+pub fn run_fixture_teardown<S: Write, E: Write>(
+    writer: &mut S,
+    err_writer: &mut E,
+    fixtures: &mut SuiteFixtures,
+) -> Vec<TestResult> {
+    let Some((_, path)) = fixtures.file else {
+        return Vec::new();
+    };
+    if fixtures.teardown_stack.is_empty() {
+        return Vec::new();
+    }
+    print_in_function(writer, &"TEARING DOWN FIXTURES", 0);
+    let test_directory = path.parent().unwrap_or(Path::new("."));
+    let mut context = Context::new(&mut fixtures.variables, test_directory);
+    let mut outcome = BlockOutcome {
+        subtest_results: Vec::new(),
+        runtime_failure: None,
+        skip: None,
+        soft_failures: Vec::new(),
+    };
+    run_teardown(
+        &mut context,
+        writer,
+        err_writer,
+        &fixtures.teardown_stack,
+        &mut outcome,
+        0,
+    );
+    match outcome.finish(false) {
+        (Status::Success, _) => Vec::new(),
+        (status, _) => vec![TestResult::new(
+            "fixture teardown".to_owned(),
+            status,
+            Vec::new(),
+        )],
+    }
+}
+
+/// The hooks of a file, grouped by when they run around its tests
+struct FileHooks<'a> {
+    before_all: Vec<&'a Function>,
+    before_each: Vec<&'a Function>,
+    after_each: Vec<&'a Function>,
+    after_all: Vec<&'a Function>,
+}
+
+impl<'a> FileHooks<'a> {
+    fn new(functions: &'a [Function]) -> Self {
+        let with_hook = |hook: &str| -> Vec<&'a Function> {
+            functions
+                .iter()
+                .filter(|function| function.hook() == Some(hook))
+                .collect()
+        };
+        Self {
+            before_all: with_hook("before-all"),
+            before_each: with_hook("before-each"),
+            after_each: with_hook("after-each"),
+            after_all: with_hook("after-all"),
+        }
+    }
+}
+
+/// Run the selected test functions of a file along with the file's hooks. The file starts with a copy
+/// of the variables set by the suite's fixtures. Variables set by the `[before-all]` hooks are copied
+/// into every test, and the `[after-all]` hooks see them as they were left by the `[before-all]` hooks
+fn run_file<S: Write, E: Write>(
+    writer: &mut S,
+    err_writer: &mut E,
+    selection: FileSelection,
+    path: &Path,
+    fixtures: &mut SuiteFixtures,
 ) -> Vec<TestResult> {
     let mut results: Vec<TestResult> = Vec::new();
     print_in_function(
         writer,
         &format!(
             "RUNNING FILE {}",
-            filename.to_str().expect("Failed to convert OsStr to path")
+            path.file_name()
+                .and_then(|filename| filename.to_str())
+                .expect("Failed to get the file name of a test file")
         ),
         0,
     );
-    for function in ast.functions {
-        if function.is_test_function() {
-            let mut function_variables = VariableMap::new();
-            results.push(run_test_function(
+    let test_directory = path.parent().unwrap_or(Path::new("."));
+    let FileSelection {
+        ast: ChimeraScriptAST { functions, params },
+        selected,
+        excluded,
+    } = selection;
+    let tests: Vec<&Function> = functions
+        .iter()
+        .filter(|function| is_test(function) && selected.contains(&function.name))
+        .collect();
+    // Setting up and tearing down a file is wasted when none of its tests will run
+    if tests.is_empty() {
+        return results;
+    }
+    fixtures.set_up(writer, err_writer);
+    let hooks = FileHooks::new(&functions);
+
+    let mut file_variables = VariableMap::new();
+    let setup_failure = match &fixtures.failure {
+        // A file is neither set up nor torn down when the suite it belongs to could not be set up
+        Some(reason) => Some(reason.clone()),
+        None => match fixtures.variables.deep_copy() {
+            Ok(variables) => {
+                file_variables = variables;
+                run_hooks(
+                    writer,
+                    err_writer,
+                    "before-all",
+                    &hooks.before_all,
+                    &mut file_variables,
+                    test_directory,
+                )
+                .err()
+            }
+            Err(error) => Some(error.to_string()),
+        },
+    };
+    // Whether each test which has run passed, for the tests which depend on it. Tests are ordered so
+    // every test runs after the tests it depends on
+    let mut passed: HashMap<&str, bool> = HashMap::new();
+    for function in tests {
+        let unmet_dependency = function.dependencies().into_iter().find_map(|dependency| {
+            match passed.get(dependency) {
+                Some(true) => None,
+                Some(false) => Some(format!("depends on {}, which did not pass", dependency)),
+                None => Some(format!("depends on {}, which was not run", dependency)),
+            }
+        });
+        let function_results = match unmet_dependency {
+            _ if excluded.contains(&function.name) => vec![skip_test_function(
                 writer,
-                err_writer,
                 function,
-                &mut function_variables,
+                "excluded by --exclude-tag, but depended on",
                 1,
+            )],
+            Some(reason) => vec![skip_test_function(writer, function, &reason, 1)],
+            None => run_top_level_function(
+                writer,
+                err_writer,
+                function,
+                &params,
+                &hooks,
+                &file_variables,
+                setup_failure.as_deref(),
+                test_directory,
+            ),
+        };
+        passed.insert(
+            function.name.as_str(),
+            function_results
+                .iter()
+                .all(|result| result.passed_with_nested_cases()),
+        );
+        results.extend(function_results);
+    }
+    if fixtures.failure.is_some() {
+        return results;
+    }
+    // Nothing depends on the [after-all] hooks, so one which fails is reported as its own result
+    if let Err(reason) = run_hooks(
+        writer,
+        err_writer,
+        "after-all",
+        &hooks.after_all,
+        &mut file_variables,
+        test_directory,
+    ) {
+        let error = ChimeraRuntimeFailure::HookFailure(reason);
+        error.print_error(err_writer, 0);
+        results.push(TestResult::new(
+            "after-all".to_owned(),
+            Status::Error(error, Vec::new()),
+            Vec::new(),
+        ));
+    }
+    results
+}
+
+/// Run each of the hooks of one kind with the given variables. A failing `before` hook stops the hooks
+/// after it from running, but every `after` hook runs so each has the chance to clean up. Returns why
+/// the hooks failed, if one did not pass
+fn run_hooks<S: Write, E: Write>(
+    writer: &mut S,
+    err_writer: &mut E,
+    kind: &str,
+    hooks: &[&Function],
+    variable_map: &mut VariableMap,
+    test_directory: &Path,
+) -> Result<(), String> {
+    let mut failure: Option<String> = None;
+    for hook in hooks {
+        let result = run_test_function(
+            writer,
+            err_writer,
+            hook,
+            variable_map,
+            test_directory,
+            None,
+            1,
+        );
+        // A hook which skips itself has nothing to set up or clean up, so it does not fail
+        let failed = matches!(result.status, Status::Failure(_) | Status::Error(..));
+        if failed && failure.is_none() {
+            failure = Some(format!(
+                "The [{}] case {} did not pass: {}",
+                kind, hook.name, result.status
             ));
+            if kind.starts_with("before") {
+                break;
+            }
         }
     }
-    results
+    match failure {
+        Some(reason) => Err(reason),
+        None => Ok(()),
+    }
 }
 
-pub fn run_function_by_name<S: Write, E: Write>(
+/// Run a test function which is not nested in another. A function with a `[params=name]` decorator
+/// is run once for every row of the PARAMS block with that name, with the row's variables set
+#[allow(clippy::too_many_arguments)]
+fn run_top_level_function<S: Write, E: Write>(
     writer: &mut S,
     err_writer: &mut E,
-    ast: ChimeraScriptAST,
-    filename: &OsStr,
-    function_name: &str,
+    function: &Function,
+    params: &[ParamsBlock],
+    hooks: &FileHooks,
+    file_variables: &VariableMap,
+    setup_failure: Option<&str>,
+    test_directory: &Path,
 ) -> Vec<TestResult> {
-    let mut results: Vec<TestResult> = Vec::new();
-    print_in_function(
-        writer,
-        &format!(
-            "RUNNING FILE {}",
-            filename.to_str().expect("Failed to convert OsStr to path")
+    // A skipped function is reported once, rather than once for each row it would have run, and
+    // without setting it up
+    if function.skip_reason().is_some() {
+        return vec![run_test_function(
+            writer,
+            err_writer,
+            function,
+            &mut VariableMap::new(),
+            test_directory,
+            None,
+            1,
+        )];
+    }
+    let rows = match function.get_value("params") {
+        Some(params_name) => load_rows(
+            params
+                .iter()
+                .find(|params| params.name == params_name)
+                .expect("A test's PARAMS are checked to be defined when it is compiled"),
+            test_directory,
         ),
-        0,
-    );
-    for function in ast.functions {
-        if function.is_test_function() && function.has_name(function_name) {
-            let mut function_variables = VariableMap::new();
-            results.push(run_test_function(
+        None => Ok(vec![Vec::new()]),
+    };
+    let rows = match rows {
+        Ok(rows) => rows,
+        Err(error) => {
+            return vec![errored_test(writer, err_writer, &function.name, error)];
+        }
+    };
+    let parametrized = function.get_value("params").is_some();
+    let mut results: Vec<TestResult> = Vec::new();
+    for (row_index, row) in rows.into_iter().enumerate() {
+        let name = match parametrized {
+            true => format!("{}[{}]", function.name, row_index),
+            false => function.name.clone(),
+        };
+        if let Some(reason) = setup_failure {
+            let error = ChimeraRuntimeFailure::HookFailure(reason.to_owned());
+            results.push(errored_test(writer, err_writer, &name, error));
+            continue;
+        }
+        let mut function_variables = match file_variables.deep_copy() {
+            Ok(function_variables) => function_variables,
+            Err(error) => {
+                results.push(errored_test(writer, err_writer, &name, error));
+                continue;
+            }
+        };
+        for (variable_name, value) in row {
+            function_variables.insert(variable_name, value);
+        }
+        let result = match run_hooks(
+            writer,
+            err_writer,
+            "before-each",
+            &hooks.before_each,
+            &mut function_variables,
+            test_directory,
+        ) {
+            Ok(()) => run_test_case(
                 writer,
                 err_writer,
                 function,
+                &name,
                 &mut function_variables,
+                test_directory,
+                None,
                 1,
-            ));
-        }
+            ),
+            Err(reason) => errored_test(
+                writer,
+                err_writer,
+                &name,
+                ChimeraRuntimeFailure::HookFailure(reason),
+            ),
+        };
+        // A test which passed is only as good as the cleanup after it
+        let after_each = run_hooks(
+            writer,
+            err_writer,
+            "after-each",
+            &hooks.after_each,
+            &mut function_variables,
+            test_directory,
+        );
+        results.push(match after_each {
+            Err(reason) if result.passed() => {
+                let error = ChimeraRuntimeFailure::HookFailure(reason);
+                error.print_error(err_writer, 1);
+                TestResult::new(
+                    result.name,
+                    Status::Error(error, Vec::new()),
+                    result.subtest_results,
+                )
+                .with_attempts(result.attempts)
+            }
+            _ => result,
+        });
     }
     results
 }
 
+/// Report a test which could not be run because of an error, without running any of it
+fn errored_test<S: Write, E: Write>(
+    writer: &mut S,
+    err_writer: &mut E,
+    name: &str,
+    error: ChimeraRuntimeFailure,
+) -> TestResult {
+    print_in_function(writer, &format!("STARTING TEST - {}", name), 1);
+    let timer = Timer::new();
+    error.print_error(err_writer, 1);
+    let status = Status::Error(error, Vec::new());
+    print_in_function(
+        writer,
+        &format!(
+            "FINISHED TEST - {} - {} - {}",
+            name,
+            timer.finish(),
+            &status
+        ),
+        1,
+    );
+    TestResult::new(name.to_owned(), status, Vec::new())
+}
+
 pub fn print_in_function<W: Write>(writer: &mut W, thing: &impl Display, depth: usize) {
     // This formats an empty string to be padded rightwards by `depth`
     // Cannot directly add padding to `thing` because padding is conditionally added to things shorter than the
@@ -289,64 +952,243 @@ pub fn print_in_function<W: Write>(writer: &mut W, thing: &impl Display, depth:
 pub fn run_test_function<S: Write, E: Write>(
     writer: &mut S,
     err_writer: &mut E,
-    function: Function,
+    function: &Function,
     variable_map: &mut VariableMap,
+    test_directory: &Path,
+    parent_deadline: Option<Deadline>,
     depth: usize,
 ) -> TestResult {
-    print_in_function(writer, &format!("STARTING TEST - {}", function.name), depth);
+    run_test_case(
+        writer,
+        err_writer,
+        function,
+        &function.name,
+        variable_map,
+        test_directory,
+        parent_deadline,
+        depth,
+    )
+}
+
+/// Run a test function, reporting it under `name` which can differ from the function's own
+/// name, such as for each row of a parametrized test
+#[allow(clippy::too_many_arguments)]
+fn run_test_case<S: Write, E: Write>(
+    writer: &mut S,
+    err_writer: &mut E,
+    function: &Function,
+    name: &str,
+    variable_map: &mut VariableMap,
+    test_directory: &Path,
+    parent_deadline: Option<Deadline>,
+    depth: usize,
+) -> TestResult {
+    if let Some(reason) = function.skip_reason() {
+        return skip_test_function(writer, function, &reason, depth);
+    }
+    print_in_function(writer, &format!("STARTING TEST - {}", name), depth);
     let timer = Timer::new();
-    let mut context = Context::new(variable_map);
+    // A [retry=N] test is run up to N more times until it passes
+    let max_attempts = function.retries().saturating_add(1);
+
+    let mut attempt: u32 = 1;
+    let (status, subtest_results) = loop {
+        // Every attempt starts from the variables the test started with, so a failed attempt
+        // cannot leave anything behind for the next one
+        let starting_variables = match max_attempts > 1 {
+            true => match variable_map.deep_copy() {
+                Ok(copy) => Some(copy),
+                Err(error) => {
+                    error.print_error(err_writer, depth);
+                    break (Status::Error(error, Vec::new()), Vec::new());
+                }
+            },
+            false => None,
+        };
+        let (status, subtest_results) = run_test_attempt(
+            writer,
+            err_writer,
+            function,
+            variable_map,
+            test_directory,
+            parent_deadline,
+            depth,
+        );
+        let retryable = matches!(status, Status::Failure(_) | Status::Error(..));
+        match starting_variables {
+            Some(starting_variables) if retryable && attempt < max_attempts => {
+                print_in_function(
+                    writer,
+                    &format!(
+                        "RETRYING TEST - {} - attempt {} of {} was a {}",
+                        name, attempt, max_attempts, status
+                    ),
+                    depth,
+                );
+                *variable_map = starting_variables;
+                attempt += 1;
+            }
+            _ => break (status, subtest_results),
+        }
+    };
+
+    let time_to_run = timer.finish();
+    let attempts_note = match attempt {
+        1 => String::new(),
+        _ => format!(" - after {} attempts", attempt),
+    };
+    print_in_function(
+        writer,
+        &format!(
+            "FINISHED TEST - {} - {} - {}{}",
+            name, time_to_run, &status, attempts_note
+        ),
+        depth,
+    );
+    TestResult::new(name.to_owned(), status, subtest_results).with_attempts(attempt)
+}
+
+/// Run a test function's block once, along with its teardown, returning its status and the results of
+/// its nested cases
+fn run_test_attempt<S: Write, E: Write>(
+    writer: &mut S,
+    err_writer: &mut E,
+    function: &Function,
+    variable_map: &mut VariableMap,
+    test_directory: &Path,
+    parent_deadline: Option<Deadline>,
+    depth: usize,
+) -> (Status, Vec<TestResult>) {
+    let mut context = Context::new(variable_map, test_directory);
+    let deadline = match function.timeout() {
+        Some(budget) => Some(Deadline::new(budget).earliest(parent_deadline)),
+        None => parent_deadline,
+    };
+    context.set_deadline(deadline);
     // TODO: If the ability to call functions is added (like calling an init function) the teardown stack needs to be
     //       passed as a mut reference into that function so it can add teardown to the stack. Should only be able
     //       to call non-test functions with no parents?
-    let mut teardown_stack: Vec<Statement> = Vec::new();
+    let mut teardown_stack: Vec<&Teardown> = Vec::new();
+    let mut outcome = run_block(
+        &mut context,
+        writer,
+        err_writer,
+        function,
+        &mut teardown_stack,
+        depth,
+    );
+
+    // The last statement may have finished after the deadline
+    if outcome.runtime_failure.is_none() {
+        if let Err(timeout) = context.check_deadline() {
+            timeout.print_error(err_writer, depth);
+            outcome.runtime_failure = Some(timeout);
+        }
+    }
 
-    // Copy these two variables here as they are needed at the end of the function and the for..in.. is about to consume function
-    let is_expected_failure = function.is_expected_failure();
-    let function_name = function.name;
+    // Teardown runs however the test ended, so it is not held to the test's deadline
+    context.set_deadline(None);
+    run_teardown(
+        &mut context,
+        writer,
+        err_writer,
+        &teardown_stack,
+        &mut outcome,
+        depth,
+    );
+    outcome.finish(function.is_expected_failure())
+}
 
+/// How running the block of a function went
+struct BlockOutcome {
+    subtest_results: Vec<TestResult>,
+    // The failure which stopped the block, if one did
+    runtime_failure: Option<ChimeraRuntimeFailure>,
+    // The reason given by a SKIP statement which stopped the block, if one did
+    skip: Option<String>,
+    soft_failures: Vec<ChimeraRuntimeFailure>,
+}
+
+impl BlockOutcome {
+    /// The status of the function the block belongs to, along with the results of its nested cases
+    fn finish(mut self, is_expected_failure: bool) -> (Status, Vec<TestResult>) {
+        // A failed EXPECT fails the test at the end, along with the failure which stopped it if any
+        if let Some(ChimeraRuntimeFailure::TestFailure(_, _)) = self.runtime_failure {
+            self.soft_failures.extend(self.runtime_failure.take());
+        }
+        let status = match self.runtime_failure {
+            Some(error) => Status::Error(error, self.soft_failures),
+            // Failed EXPECT statements before a SKIP still fail the test
+            None if !self.soft_failures.is_empty() => match is_expected_failure {
+                true => Status::ExpectedFailure,
+                false => Status::Failure(self.soft_failures),
+            },
+            None => match (self.skip, is_expected_failure) {
+                (Some(reason), _) => Status::Skip(reason),
+                (None, true) => Status::UnexpectedSuccess,
+                (None, false) => Status::Success,
+            },
+        };
+        (status, self.subtest_results)
+    }
+}
+
+/// Run the statements and nested cases of a function until one stops it, pushing each teardown block
+/// it reaches onto the teardown stack
+fn run_block<'f, S: Write, E: Write>(
+    context: &mut Context,
+    writer: &mut S,
+    err_writer: &mut E,
+    function: &'f Function,
+    teardown_stack: &mut Vec<&'f Teardown>,
+    depth: usize,
+) -> BlockOutcome {
     let mut subtest_results: Vec<TestResult> = Vec::new();
     let mut runtime_failure: Option<ChimeraRuntimeFailure> = None;
+    let mut skip: Option<String> = None;
+    let mut soft_failures: Vec<ChimeraRuntimeFailure> = Vec::new();
 
-    for block_contents in function.block {
+    for block_contents in &function.block {
         match block_contents {
-            BlockContents::Function(nested_function) => subtest_results.push(run_test_function(
-                writer,
-                err_writer,
-                nested_function,
-                context.variable_map,
-                depth + 1,
-            )),
-            BlockContents::Teardown(mut teardown_block) => {
+            BlockContents::Function(nested_function) => {
+                // A nested case is not started once the deadline it would share has passed
+                if let Err(timeout) = context.check_deadline() {
+                    timeout.print_error(err_writer, depth);
+                    runtime_failure = Some(timeout);
+                    break;
+                }
+                subtest_results.push(run_test_function(
+                    writer,
+                    err_writer,
+                    nested_function,
+                    context.variable_map,
+                    context.test_directory,
+                    context.deadline,
+                    depth + 1,
+                ))
+            }
+            BlockContents::Teardown(teardown_block) => {
                 // TODO: Swap any Value::Variable uses in each statement for a Value::Literal to "stabilize" the
                 //       teardown statement against any variable changes during the test
-                teardown_stack.append(&mut teardown_block.statements);
+                teardown_stack.push(teardown_block);
             }
             BlockContents::Statement(statement) => {
-                // Run statement
                 // Match on the specific kind of runtime failure. If we have a TestFailure then we want to mark
                 // this_test_passed, print the failure, and continue.
                 // If we have any other runtime error, just return the error
-                let statement_result = match statement {
-                    Statement::AssertCommand(assert_command) => {
-                        crate::commands::assert::assert_command(&context, &assert_command)
-                    }
-                    Statement::AssignmentExpr(assert_expr) => {
-                        crate::commands::assignment::assignment_command(&mut context, assert_expr)
-                    }
-                    Statement::PrintCommand(print_cmd) => {
-                        crate::commands::print::print_command(&context, writer, print_cmd, depth)
-                    }
-                    Statement::Expression(expr) => {
-                        // We are running an expression without assigning it, we can toss the result
-                        match crate::commands::expression::expression_command(&context, expr) {
-                            Ok(_) => Ok(()),
-                            Err(e) => Err(e),
-                        }
-                    }
-                };
+                let statement_result = run_statement(context, writer, statement, depth);
+                // Failed EXPECT statements are printed as they happen, but do not stop the test
+                for soft_failure in context.take_soft_failures() {
+                    soft_failure.print_error(err_writer, depth);
+                    soft_failures.push(soft_failure);
+                }
                 match statement_result {
-                    Ok(_) => (),
+                    Ok(StatementOutcome::Continue) => (),
+                    // A SKIP ends the test, but is not an error so it is only reported by the status
+                    Ok(StatementOutcome::Skip(reason)) => {
+                        skip = Some(reason);
+                        break;
+                    }
                     Err(runtime_error) => {
                         runtime_error.print_error(err_writer, depth);
                         runtime_failure = Some(runtime_error);
@@ -357,34 +1199,152 @@ pub fn run_test_function<S: Write, E: Write>(
         };
         context.current_line += 1;
     }
+    BlockOutcome {
+        subtest_results,
+        runtime_failure,
+        skip,
+        soft_failures,
+    }
+}
 
-    // TODO: When the test function ends, process the teardown stack
+/// Run a teardown stack, the most recently added block first so things are undone in the reverse
+/// order they were done. A failed teardown only changes the outcome of a block which otherwise passed
+fn run_teardown<S: Write, E: Write>(
+    context: &mut Context,
+    writer: &mut S,
+    err_writer: &mut E,
+    teardown_stack: &[&Teardown],
+    outcome: &mut BlockOutcome,
+    depth: usize,
+) {
+    for teardown_block in teardown_stack.iter().rev() {
+        for statement in &teardown_block.statements {
+            let statement_result = run_statement(context, writer, statement, depth);
+            // Failed EXPECT statements in teardown are reported the same as any other teardown failure
+            for soft_failure in context.take_soft_failures() {
+                soft_failure.print_error(err_writer, depth);
+                outcome.soft_failures.push(soft_failure);
+            }
+            match statement_result {
+                Ok(StatementOutcome::Continue) => (),
+                // A SKIP in teardown skips a block which otherwise passed
+                Ok(StatementOutcome::Skip(reason)) => {
+                    outcome.skip.get_or_insert(reason);
+                }
+                Err(teardown_error) => {
+                    teardown_error.print_error(err_writer, depth);
+                    if outcome.runtime_failure.is_none() && outcome.soft_failures.is_empty() {
+                        outcome.runtime_failure = Some(teardown_error);
+                    }
+                }
+            }
+        }
+    }
+}
 
-    let status = match runtime_failure {
-        Some(failure_reason) => match failure_reason {
-            ChimeraRuntimeFailure::TestFailure(_, _) => match is_expected_failure {
-                true => Status::ExpectedFailure,
-                false => Status::Failure(failure_reason),
-            },
-            _ => Status::Error(failure_reason),
-        },
-        None => match is_expected_failure {
-            true => Status::UnexpectedSuccess,
-            false => Status::Success,
-        },
-    };
-    let time_to_run = timer.finish();
+/// Skip a function without running any of it, along with every case nested inside of it
+fn skip_test_function<S: Write>(
+    writer: &mut S,
+    function: &Function,
+    reason: &str,
+    depth: usize,
+) -> TestResult {
     print_in_function(
         writer,
-        &format!(
-            "FINISHED TEST - {} - {} - {}",
-            function_name.as_str(),
-            time_to_run,
-            &status
-        ),
+        &format!("SKIPPED TEST - {} - {}", function.name, reason),
         depth,
     );
-    TestResult::new(function_name, status, subtest_results)
+    let subtest_results = function
+        .block
+        .iter()
+        .filter_map(|block_contents| match block_contents {
+            BlockContents::Function(nested_function) => Some(skip_test_function(
+                writer,
+                nested_function,
+                reason,
+                depth + 1,
+            )),
+            _ => None,
+        })
+        .collect();
+    TestResult::new(
+        function.name.clone(),
+        Status::Skip(reason.to_owned()),
+        subtest_results,
+    )
+}
+
+/// Run a single statement, printing any verbose messages it logged when running in verbose mode
+/// How a statement which did not fail ended
+#[derive(Debug, PartialEq)]
+pub enum StatementOutcome {
+    /// The statement finished and the statements after it can run
+    Continue,
+    /// A SKIP statement ended the test, with the reason it gave
+    Skip(String),
+}
+
+pub fn run_statement<W: Write>(
+    context: &mut Context,
+    writer: &mut W,
+    statement: &Statement,
+    depth: usize,
+) -> Result<StatementOutcome, ChimeraRuntimeFailure> {
+    context.check_deadline()?;
+    let statement_result = match statement {
+        Statement::AssertCommand(assert_command) => {
+            crate::commands::assert::assert_command(context, assert_command)
+                .map(|_| StatementOutcome::Continue)
+        }
+        Statement::AssignmentExpr(assert_expr) => {
+            crate::commands::assignment::assignment_command(context, assert_expr)
+                .map(|_| StatementOutcome::Continue)
+        }
+        Statement::PrintCommand(print_cmd) => {
+            crate::commands::print::print_command(context, writer, print_cmd, depth)
+                .map(|_| StatementOutcome::Continue)
+        }
+        Statement::SleepCommand(seconds) => crate::commands::sleep::sleep_command(context, seconds)
+            .map(|_| StatementOutcome::Continue),
+        Statement::SkipCommand(reason) => crate::commands::end_test::skip_command(context, reason),
+        Statement::FailCommand(reason) => crate::commands::end_test::fail_command(context, reason)
+            .map(|_| StatementOutcome::Continue),
+        Statement::Expression(expr) => {
+            // We are running an expression without assigning it, we can toss the result
+            match crate::commands::expression::expression_command(context, expr) {
+                Ok(_) => Ok(StatementOutcome::Continue),
+                Err(e) => Err(e),
+            }
+        }
+        Statement::Eventually(eventually_block) => crate::commands::eventually::eventually_command(
+            context,
+            writer,
+            eventually_block,
+            depth,
+        ),
+        Statement::AssertError(assert_error_block) => {
+            crate::commands::assert_error::assert_error_command(
+                context,
+                writer,
+                assert_error_block,
+                depth,
+            )
+        }
+    };
+    let verbose_log = context.take_verbose_log();
+    if crate::VERBOSE.get().copied().unwrap_or(false) {
+        for message in verbose_log {
+            print_in_function(writer, &message, depth);
+        }
+    }
+    // A statement cut short by the deadline, like a request only given the time left, timed out
+    match statement_result {
+        Err(runtime_error) => {
+            context.check_deadline()?;
+            Err(runtime_error)
+        }
+        outcome => outcome,
+    }
 }
 
 fn handle_ast_err(e: pest::error::Error<Rule>) -> ChimeraCompileError {
@@ -2,7 +2,8 @@ use crate::abstract_syntax_tree::{Statement, Value};
 use crate::err_handle::{ChimeraRuntimeFailure, VarTypes};
 use crate::frontend::Context;
 use serde::de::{Error, MapAccess, SeqAccess, Visitor};
-use serde::{Deserialize, Deserializer};
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::cell::{Ref, RefCell, RefMut};
 use std::cmp::Ordering;
 use std::collections::HashMap;
@@ -116,6 +117,24 @@ impl Display for NumberKind {
 }
 
 impl NumberKind {
+    // Numbers which are equal get the same key, no matter which kind they are. Comparing an integer
+    // with a float converts the integer into a float, so every number is keyed by its float value.
+    // Numbers can share a key without being equal, like two integers past 2^53 or two NaNs
+    fn unordered_key(self) -> String {
+        let float = self.to_f64();
+        match float.is_nan() {
+            true => "NaN".to_owned(),
+            // Adding zero turns -0.0 into 0.0, which it equals
+            false => format!("{:x}", (float + 0.0).to_bits()),
+        }
+    }
+    pub fn to_f64(self) -> f64 {
+        match self {
+            NumberKind::I64(signed) => signed as f64,
+            NumberKind::U64(unsigned) => unsigned as f64,
+            NumberKind::F64(float) => float,
+        }
+    }
     pub fn to_usize(self) -> Option<usize> {
         match self {
             NumberKind::I64(signed) => usize::try_from(signed).ok(),
@@ -170,7 +189,7 @@ impl Data {
             handle: Rc::new(RefCell::new(DataKind::Collection(Collection::List(v)))),
         }
     }
-    pub fn borrow(&self) -> Result<Ref<DataKind>, ChimeraRuntimeFailure> {
+    pub fn borrow(&self) -> Result<Ref<'_, DataKind>, ChimeraRuntimeFailure> {
         match self.handle.try_borrow() {
             // Must return a Ref<T> here, returning a Ref<T>::deref() will error.
             // This happens because RefCell<T>::try_borrow returns a Ref<T> with the lifetime of the &self passed into
@@ -183,7 +202,27 @@ impl Data {
             )),
         }
     }
-    pub fn borrow_mut(&self) -> Result<RefMut<DataKind>, ChimeraRuntimeFailure> {
+    /// Copy the data rather than the handle to it, so changes to the copy are not seen here
+    pub fn deep_copy(&self) -> Result<Self, ChimeraRuntimeFailure> {
+        let copied = match self.borrow()?.deref() {
+            DataKind::Literal(literal) => DataKind::Literal(literal.clone()),
+            DataKind::Collection(Collection::List(list)) => DataKind::Collection(Collection::List(
+                list.iter()
+                    .map(|item| item.deep_copy())
+                    .collect::<Result<Vec<Data>, ChimeraRuntimeFailure>>()?,
+            )),
+            DataKind::Collection(Collection::Object(object)) => {
+                DataKind::Collection(Collection::Object(
+                    object
+                        .iter()
+                        .map(|(key, value)| Ok((key.clone(), value.deep_copy()?)))
+                        .collect::<Result<HashMap<String, Data>, ChimeraRuntimeFailure>>()?,
+                ))
+            }
+        };
+        Ok(Data::new(copied))
+    }
+    pub fn borrow_mut(&self) -> Result<RefMut<'_, DataKind>, ChimeraRuntimeFailure> {
         match self.handle.try_borrow_mut() {
             Ok(d) => Ok(d),
             Err(_) => Err(ChimeraRuntimeFailure::BorrowError(
@@ -283,7 +322,104 @@ impl PartialEq for DataKind {
     }
 }
 
+/// Type names used by TYPE assertions and TYPEOF expressions
+pub const TYPE_NAMES: [&str; 9] = [
+    "string", "number", "unsigned", "integer", "float", "bool", "null", "list", "object",
+];
+
 impl DataKind {
+    /// The general type of this data, one of "string", "number", "bool", "null", "list", or "object"
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Literal(Literal::String(_)) => "string",
+            Self::Literal(Literal::Number(_)) => "number",
+            Self::Literal(Literal::Bool(_)) => "bool",
+            Self::Literal(Literal::Null) => "null",
+            Self::Collection(Collection::List(_)) => "list",
+            Self::Collection(Collection::Object(_)) => "object",
+        }
+    }
+    /// Check if this data is of a type from `TYPE_NAMES`. Numbers can also be checked against
+    /// the narrower "unsigned", "integer", and "float" types
+    pub fn is_type(&self, type_name: &str) -> bool {
+        match type_name {
+            "unsigned" => matches!(self.to_number(), Some(NumberKind::U64(_))),
+            "integer" => matches!(
+                self.to_number(),
+                Some(NumberKind::U64(_)) | Some(NumberKind::I64(_))
+            ),
+            "float" => matches!(self.to_number(), Some(NumberKind::F64(_))),
+            _ => self.type_name() == type_name,
+        }
+    }
+    /// Check if this data equals `other` when every list, at any depth, is compared as a multiset
+    pub fn unordered_eq(&self, other: &DataKind) -> Result<bool, ChimeraRuntimeFailure> {
+        match (self, other) {
+            (
+                Self::Collection(Collection::List(list)),
+                Self::Collection(Collection::List(other_list)),
+            ) => {
+                if list.len() != other_list.len() {
+                    return Ok(false);
+                }
+                let matched_to = unordered_matches(list, other_list)?;
+                Ok(matched_to.iter().all(|matched| matched.is_some()))
+            }
+            (
+                Self::Collection(Collection::Object(object)),
+                Self::Collection(Collection::Object(other_object)),
+            ) => {
+                if object.len() != other_object.len() {
+                    return Ok(false);
+                }
+                for (key, value) in object {
+                    match other_object.get(key) {
+                        Some(other_value) => {
+                            if !value.borrow()?.unordered_eq(&*other_value.borrow()?)? {
+                                return Ok(false);
+                            }
+                        }
+                        None => return Ok(false),
+                    }
+                }
+                Ok(true)
+            }
+            _ => Ok(self == other),
+        }
+    }
+    // A key which is the same for two pieces of data when they are equal without regard to the
+    // order of list members. Data with the same key is not always equal, so keys only narrow down
+    // which members of a list are compared with `unordered_eq`
+    fn unordered_key(&self) -> Result<String, ChimeraRuntimeFailure> {
+        let key = match self {
+            Self::Literal(Literal::String(s)) => {
+                serde_json::to_string(s).unwrap_or_else(|_| format!("\"{}\"", s))
+            }
+            Self::Literal(Literal::Number(number)) => number.unordered_key(),
+            Self::Literal(literal) => literal.to_string(),
+            Self::Collection(Collection::Object(object)) => {
+                let mut members: Vec<String> = Vec::with_capacity(object.len());
+                for (key, value) in object {
+                    members.push(format!(
+                        "{}:{}",
+                        serde_json::to_string(key).unwrap_or_else(|_| key.clone()),
+                        value.borrow()?.unordered_key()?
+                    ));
+                }
+                members.sort();
+                format!("{{{}}}", members.join(","))
+            }
+            Self::Collection(Collection::List(list)) => {
+                let mut members: Vec<String> = Vec::with_capacity(list.len());
+                for member in list {
+                    members.push(member.borrow()?.unordered_key()?);
+                }
+                members.sort();
+                format!("[{}]", members.join(","))
+            }
+        };
+        Ok(key)
+    }
     pub fn to_number(&self) -> Option<NumberKind> {
         match self {
             Self::Collection(_) => None,
@@ -393,7 +529,7 @@ impl PartialEq for Collection {
                         return false;
                     };
                     self_obj.iter().all(|(key, value)| {
-                        other_obj.get(key).map_or(false, |v| {
+                        other_obj.get(key).is_some_and(|v| {
                             v.borrow().expect("Failed to borrow object member").deref()
                                 == value
                                     .borrow()
@@ -459,19 +595,29 @@ impl Collection {
             _ => None,
         }
     }
+    /// Check if a list has a member equal to `contains_data`, or an object has it as a key. When
+    /// `unordered` is set, lists inside of list members are compared without regard to order
     pub fn contains(
         &self,
-        contains_data: Ref<DataKind>,
+        contains_data: &DataKind,
+        unordered: bool,
         context: &Context,
     ) -> Result<bool, ChimeraRuntimeFailure> {
         match self {
+            Collection::List(list) if unordered => {
+                for member in list {
+                    if member.borrow()?.unordered_eq(contains_data)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
             Collection::List(list) => {
                 let borrowed_list_values: Result<Vec<_>, ChimeraRuntimeFailure> =
                     list.iter().map(|x| x.borrow()).collect();
-                let rhs = contains_data.deref();
                 let res = borrowed_list_values?
                     .into_iter()
-                    .any(|member| member.deref() == rhs);
+                    .any(|member| member.deref() == contains_data);
                 Ok(res)
             }
             Collection::Object(map) => {
@@ -484,6 +630,72 @@ impl Collection {
     }
 }
 
+/// Pair each member of `expected` with a different member of `actual` which it equals without
+/// regard to the order of lists. Returns the index of the expected member each actual member is
+/// paired with, or None for an actual member left without a pair
+pub fn unordered_matches(
+    actual: &[Data],
+    expected: &[Data],
+) -> Result<Vec<Option<usize>>, ChimeraRuntimeFailure> {
+    let mut by_key: HashMap<String, Vec<usize>> = HashMap::new();
+    for (index, member) in actual.iter().enumerate() {
+        by_key
+            .entry(member.borrow()?.unordered_key()?)
+            .or_default()
+            .push(index);
+    }
+    let mut candidates: Vec<Vec<usize>> = Vec::with_capacity(expected.len());
+    for expected_member in expected {
+        let expected_member = expected_member.borrow()?;
+        let mut matching: Vec<usize> = Vec::new();
+        if let Some(indexes) = by_key.get(&expected_member.unordered_key()?) {
+            for &index in indexes {
+                if actual[index].borrow()?.unordered_eq(&expected_member)? {
+                    matching.push(index);
+                }
+            }
+        }
+        candidates.push(matching);
+    }
+    Ok(match_candidates(actual.len(), &candidates))
+}
+
+/// Pair each expected member with a different one of its `candidates`, the indexes of the actual
+/// members it matches. A member can match more than one other, so this finds a maximum matching by
+/// moving earlier pairs aside when they have another option. Returns the expected index each actual
+/// member is paired with
+pub fn match_candidates(actual_len: usize, candidates: &[Vec<usize>]) -> Vec<Option<usize>> {
+    let mut matched_to: Vec<Option<usize>> = vec![None; actual_len];
+    for expected_index in 0..candidates.len() {
+        let mut visited = vec![false; actual_len];
+        find_match(expected_index, candidates, &mut matched_to, &mut visited);
+    }
+    matched_to
+}
+
+fn find_match(
+    expected_index: usize,
+    candidates: &[Vec<usize>],
+    matched_to: &mut [Option<usize>],
+    visited: &mut [bool],
+) -> bool {
+    for &actual_index in &candidates[expected_index] {
+        if visited[actual_index] {
+            continue;
+        }
+        visited[actual_index] = true;
+        let is_free = match matched_to[actual_index] {
+            None => true,
+            Some(other) => find_match(other, candidates, matched_to, visited),
+        };
+        if is_free {
+            matched_to[actual_index] = Some(expected_index);
+            return true;
+        }
+    }
+    false
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Literal {
     String(String),
@@ -577,27 +789,8 @@ impl<'de> Deserialize<'de> for DataKind {
             where
                 E: Error,
             {
-                // Serde often interprets non-string values as strings for non-structured data like this interpreters
-                // so we need to check if we have a non-string type
-                // TODO: This fixes an issue I ran into, but this will cause a new problem of silently converting user
-                //       input if a user _wants_ to use a stringified number. If a user expects the value "5" here they
-                //       might not understand why they keep getting u64::5. This is not a permanent fix
-                if let Ok(unsigned_int) = v.parse::<u64>() {
-                    return Ok(DataKind::Literal(Literal::Number(NumberKind::U64(
-                        unsigned_int,
-                    ))));
-                }
-                if let Ok(signed_int) = v.parse::<i64>() {
-                    return Ok(DataKind::Literal(Literal::Number(NumberKind::I64(
-                        signed_int,
-                    ))));
-                }
-                if let Ok(float) = v.parse::<f64>() {
-                    return Ok(DataKind::Literal(Literal::Number(NumberKind::F64(float))));
-                }
-                if let Ok(boolean) = v.parse::<bool>() {
-                    return Ok(DataKind::Literal(Literal::Bool(boolean)));
-                }
+                // A string is always kept as a string, even if it looks like a number, so "5" and 5
+                // can be told apart
                 Ok(DataKind::Literal(Literal::String(v)))
             }
             fn visit_none<E>(self) -> Result<Self::Value, E>
@@ -649,3 +842,110 @@ impl<'de> Deserialize<'de> for DataKind {
         deserializer.deserialize_any(DatakindVisitor)
     }
 }
+
+impl Serialize for DataKind {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            DataKind::Literal(literal) => match literal {
+                Literal::String(s) => serializer.serialize_str(s),
+                Literal::Number(NumberKind::I64(signed)) => serializer.serialize_i64(*signed),
+                Literal::Number(NumberKind::U64(unsigned)) => serializer.serialize_u64(*unsigned),
+                Literal::Number(NumberKind::F64(float)) => serializer.serialize_f64(*float),
+                Literal::Bool(b) => serializer.serialize_bool(*b),
+                Literal::Null => serializer.serialize_unit(),
+            },
+            DataKind::Collection(Collection::List(list)) => {
+                let mut seq = serializer.serialize_seq(Some(list.len()))?;
+                for member in list {
+                    let borrowed = member.borrow().map_err(serde::ser::Error::custom)?;
+                    seq.serialize_element(borrowed.deref())?;
+                }
+                seq.end()
+            }
+            DataKind::Collection(Collection::Object(object)) => {
+                let mut map = serializer.serialize_map(Some(object.len()))?;
+                for (key, value) in object {
+                    let borrowed = value.borrow().map_err(serde::ser::Error::custom)?;
+                    map.serialize_entry(key, borrowed.deref())?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod literal_tests {
+    use super::*;
+
+    fn number_list(numbers: &[NumberKind]) -> DataKind {
+        DataKind::Collection(Collection::List(
+            numbers
+                .iter()
+                .map(|number| Data::from_literal(Literal::Number(*number)))
+                .collect(),
+        ))
+    }
+
+    #[test]
+    /// Test that unordered comparisons of large floats and NaN agree with EQUALS
+    fn unordered_eq_numbers() {
+        let pairs = [
+            (NumberKind::F64(1e40), NumberKind::F64(1e41)),
+            (NumberKind::F64(f64::MAX), NumberKind::F64(1e300)),
+            (NumberKind::F64(f64::INFINITY), NumberKind::F64(f64::MAX)),
+            (NumberKind::F64(1e40), NumberKind::F64(1e40)),
+            (NumberKind::F64(-0.0), NumberKind::I64(0)),
+            (NumberKind::F64(3.0), NumberKind::U64(3)),
+            (NumberKind::F64(0.5), NumberKind::F64(0.5)),
+            (NumberKind::F64(f64::NAN), NumberKind::F64(f64::NAN)),
+            (
+                NumberKind::U64(9007199254740993),
+                NumberKind::F64(9007199254740992.0),
+            ),
+            (
+                NumberKind::U64(9007199254740993),
+                NumberKind::U64(9007199254740992),
+            ),
+            (NumberKind::I64(-1), NumberKind::U64(u64::MAX)),
+        ];
+        for (left, right) in pairs {
+            let left_data = DataKind::Literal(Literal::Number(left));
+            let right_data = DataKind::Literal(Literal::Number(right));
+            assert_eq!(
+                left_data.unordered_eq(&right_data).unwrap(),
+                left == right,
+                "Expected comparing {} and {} to agree with EQUALS",
+                left,
+                right
+            );
+        }
+        let nan = f64::NAN;
+        let with_nan = number_list(&[NumberKind::F64(nan), NumberKind::F64(1e40)]);
+        assert!(!with_nan.unordered_eq(&with_nan).unwrap());
+        let large = number_list(&[NumberKind::F64(1e40), NumberKind::F64(1e39)]);
+        let reversed = number_list(&[NumberKind::F64(1e39), NumberKind::F64(1e40)]);
+        let different = number_list(&[NumberKind::F64(1e41), NumberKind::F64(1e39)]);
+        assert!(large.unordered_eq(&reversed).unwrap());
+        assert!(!large.unordered_eq(&different).unwrap());
+        // Integers past 2^53 equal the float they convert to, but not each other, so a member
+        // which equals more than one other is paired with the one left over
+        let integers = number_list(&[
+            NumberKind::U64(9007199254740993),
+            NumberKind::U64(9007199254740992),
+        ]);
+        let mixed = number_list(&[
+            NumberKind::F64(9007199254740992.0),
+            NumberKind::U64(9007199254740993),
+        ]);
+        assert!(integers.unordered_eq(&mixed).unwrap());
+        let integers_only = number_list(&[
+            NumberKind::U64(9007199254740992),
+            NumberKind::U64(9007199254740992),
+        ]);
+        assert!(!integers.unordered_eq(&integers_only).unwrap());
+    }
+}
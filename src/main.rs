@@ -8,9 +8,10 @@ mod util;
 mod variable_map;
 
 use err_handle::print_error;
-use frontend::ResultCount;
+use frontend::{ResultCount, SuiteFixtures};
 use util::client::{RealClient, WebClient};
 use util::config::Config;
+use util::tags::TagFilter;
 use util::timer::Timer;
 
 extern crate reqwest;
@@ -19,6 +20,7 @@ extern crate serde_json;
 use crate::abstract_syntax_tree::ChimeraScriptAST;
 use crate::err_handle::CLIError;
 use clap::Parser;
+use std::ffi::OsStr;
 use std::fs;
 use std::io::{stderr, stdout, Write};
 use std::path::{Path, PathBuf};
@@ -26,6 +28,8 @@ use std::str::FromStr;
 use std::sync::OnceLock;
 
 const FILE_EXTENSION: &str = "chs";
+/// The file in the directory of a suite whose `[fixture]` cases set up every file of the suite
+const FIXTURES_FILE: &str = "fixtures.chs";
 
 #[derive(Parser, Debug)]
 #[command(version)]
@@ -39,12 +43,37 @@ struct Args {
     /// Path to a config file
     #[arg(short, long)]
     config: String,
+    /// Proxy to route requests through, overrides the proxy set in the config file
+    #[arg(long)]
+    proxy: Option<String>,
+    /// Comma separated list of hosts which bypass the proxy, overrides the config file
+    #[arg(long)]
+    no_proxy: Option<String>,
+    /// Print extra information while running tests, such as retried requests
+    #[arg(short, long)]
+    verbose: bool,
+    /// Multiplier applied to the length of every SLEEP statement, 0 disables sleeping
+    #[arg(long, default_value_t = 1.0)]
+    sleep_scale: f64,
+    /// Rewrite the snapshot used by each SNAPSHOT assertion rather than comparing against it
+    #[arg(long)]
+    update_snapshots: bool,
+    /// Only run tests with this tag, can be given more than once to run tests with any of them
+    #[arg(long)]
+    tag: Vec<String>,
+    /// Do not run tests with this tag, can be given more than once
+    #[arg(long)]
+    exclude_tag: Vec<String>,
 }
 
 static CLIENT: OnceLock<&(dyn WebClient + Sync)> = OnceLock::new();
 static REAL_CLIENT: OnceLock<RealClient> = OnceLock::new();
 
 static TEST_NAME: OnceLock<Option<String>> = OnceLock::new();
+static VERBOSE: OnceLock<bool> = OnceLock::new();
+static SLEEP_SCALE: OnceLock<f64> = OnceLock::new();
+static UPDATE_SNAPSHOTS: OnceLock<bool> = OnceLock::new();
+static TAG_FILTER: OnceLock<TagFilter> = OnceLock::new();
 
 fn system_checks() {
     if !cfg!(target_pointer_width = "64") {
@@ -52,12 +81,45 @@ fn system_checks() {
     }
 }
 
+/// Run every test file of a suite, which is either a directory or a single file along with the other
+/// files in its directory. The suite's fixtures are set up before the first test runs, and torn down
+/// after the last file. When no test is selected to run, the fixtures are never set up
+fn run_suite<S: Write, E: Write>(
+    writer: &mut S,
+    error_writer: &mut E,
+    path: &Path,
+) -> Result<ResultCount, CLIError> {
+    let suite_directory = match path.is_dir() {
+        true => path,
+        false => path.parent().unwrap_or(Path::new(".")),
+    };
+    let fixtures_path = suite_directory.join(FIXTURES_FILE);
+    // Running the fixtures file on its own runs it like any other file
+    let fixtures_ast = match fixtures_path.is_file() && path != fixtures_path {
+        true => Some(read_ast(error_writer, fixtures_path.as_path())?),
+        false => None,
+    };
+
+    let mut fixtures = match &fixtures_ast {
+        Some(fixtures_ast) => SuiteFixtures::from_file(fixtures_ast, fixtures_path.as_path()),
+        None => SuiteFixtures::new(),
+    };
+    let result_count = if path.is_dir() {
+        walk_directory(writer, error_writer, path, &mut fixtures)
+    } else {
+        run_test_file(writer, error_writer, path, &mut fixtures)
+    };
+    let teardown_results = frontend::run_fixture_teardown(writer, error_writer, &mut fixtures);
+    result_count.map(|count| count + ResultCount::from_test_results(teardown_results))
+}
+
 fn walk_directory<S: Write, E: Write>(
     writer: &mut S,
     error_writer: &mut E,
     path: &Path,
+    fixtures: &mut SuiteFixtures,
 ) -> Result<ResultCount, CLIError> {
-    let mut result_count = ResultCount::new((0, 0, 0, 0));
+    let mut result_count = ResultCount::new((0, 0, 0, 0, 0, 0));
     if !path.is_dir() {
         return Err(CLIError::new(
             format!(
@@ -81,11 +143,16 @@ fn walk_directory<S: Write, E: Write>(
             {
                 continue;
             }
+            // A fixtures file sets up the suite rather than being run as a test file. Only the one at
+            // the top of the suite is used, one in a subdirectory is skipped
+            if entry_path.file_name() == Some(OsStr::new(FIXTURES_FILE)) {
+                continue;
+            }
             // Run a test file and get its results
-            let count = run_test_file(writer, error_writer, entry_path.as_path())?;
+            let count = run_test_file(writer, error_writer, entry_path.as_path(), fixtures)?;
             result_count = result_count + count;
         } else if entry_path.is_dir() {
-            let count = walk_directory(writer, error_writer, entry_path.as_path())?;
+            let count = walk_directory(writer, error_writer, entry_path.as_path(), fixtures)?;
             result_count = result_count + count;
         }
     }
@@ -96,7 +163,32 @@ fn run_test_file<S: Write, E: Write>(
     writer: &mut S,
     error_writer: &mut E,
     path: &Path,
+    fixtures: &mut SuiteFixtures,
 ) -> Result<ResultCount, CLIError> {
+    let ast = read_ast(error_writer, path)?;
+    let test_name = TEST_NAME.get().expect("TEST_NAME OnceLock was not set");
+    // TODO: What happens if multiple files have the same name?
+    //       Could pass the entire directory name here rather than a file name
+    //       Could instead indent on the folder name as we recurse into a folder?
+    let results = if test_name.is_some() {
+        let test_name = test_name.clone().unwrap();
+        frontend::run_function_by_name(
+            writer,
+            error_writer,
+            ast,
+            path,
+            fixtures,
+            TAG_FILTER.get(),
+            test_name.as_str(),
+        )
+    } else {
+        frontend::run_functions(writer, error_writer, ast, path, fixtures, TAG_FILTER.get())
+    };
+    Ok(ResultCount::from_test_results(results))
+}
+
+/// Read and compile a ChimeraScript file, printing the error if it fails to compile
+fn read_ast<E: Write>(error_writer: &mut E, path: &Path) -> Result<ChimeraScriptAST, CLIError> {
     // The file should have a .chs extension if we want to run it
     let extension = path.extension();
     if extension.is_none() || extension.unwrap() != FILE_EXTENSION {
@@ -118,39 +210,11 @@ fn run_test_file<S: Write, E: Write>(
             ))
         }
     };
-    match ChimeraScriptAST::new(file_contents.as_str()) {
-        Ok(ast) => {
-            let test_name = TEST_NAME.get().expect("TEST_NAME OnceLock was not set");
-            // TODO: What happens if multiple files have the same name?
-            //       Could pass the entire directory name here rather than a file name
-            //       Could instead indent on the folder name as we recurse into a folder?
-            let results = if test_name.is_some() {
-                let test_name = test_name.clone().unwrap();
-                frontend::run_function_by_name(
-                    writer,
-                    error_writer,
-                    ast,
-                    path.file_name()
-                        .expect("Failed to get file name when running functions"),
-                    test_name.as_str(),
-                )
-            } else {
-                frontend::run_functions(
-                    writer,
-                    error_writer,
-                    ast,
-                    path.file_name()
-                        .expect("Failed to get file name when running functions"),
-                )
-            };
-            Ok(ResultCount::from_test_results(results))
-        }
-        Err(e) => {
-            e.print_error(error_writer);
-            // This is a hack, see the comment in main() and the to-do in err_handle.rs
-            Err(CLIError::new("".to_string(), true))
-        }
-    }
+    ChimeraScriptAST::new(file_contents.as_str()).map_err(|e| {
+        // This is a hack, see the comment in main() and the to-do in err_handle.rs
+        e.print_error(error_writer);
+        CLIError::new("".to_string(), true)
+    })
 }
 
 fn main() {
@@ -158,7 +222,7 @@ fn main() {
     let args = Args::parse();
 
     // Get config from args
-    let config = match Config::from_path_str(&args.config) {
+    let mut config = match Config::from_path_str(&args.config) {
         Ok(config) => config,
         Err(err_msg) => {
             // TODO: See below todo but I should not be using direct handles to stdout or stderr
@@ -166,15 +230,19 @@ fn main() {
             return;
         }
     };
+    config.override_proxy(args.proxy, args.no_proxy);
 
     // Set the domain for our web requests. The value held by the OnceLock must have a static
     // lifetime, so the client must be placed into its own OnceLock. A little hacky, but functional.
     // The purpose of CLIENT is so the web client can be mocked by tests
-    // TODO: make a client builder here, configure it, then build the client
-    let client = RealClient::new(
-        config.get_target_address(),
-        reqwest::blocking::Client::new(),
-    );
+    let transport = match config.build_transport() {
+        Ok(transport) => transport,
+        Err(err_msg) => {
+            print_error(&mut stderr(), &err_msg);
+            return;
+        }
+    };
+    let client = RealClient::new(config.get_target_address(), transport, config.retry);
     REAL_CLIENT
         .set(client)
         .expect("Failed to set up web client");
@@ -187,6 +255,25 @@ fn main() {
     TEST_NAME
         .set(args.name)
         .expect("Failed to set TEST_NAME OnceLock");
+    VERBOSE
+        .set(args.verbose)
+        .expect("Failed to set VERBOSE OnceLock");
+    if !args.sleep_scale.is_finite() || args.sleep_scale < 0.0 {
+        print_error(
+            &mut stderr(),
+            &format!("Sleep scale {} must be 0 or greater", args.sleep_scale),
+        );
+        return;
+    }
+    SLEEP_SCALE
+        .set(args.sleep_scale)
+        .expect("Failed to set SLEEP_SCALE OnceLock");
+    UPDATE_SNAPSHOTS
+        .set(args.update_snapshots)
+        .expect("Failed to set UPDATE_SNAPSHOTS OnceLock");
+    TAG_FILTER
+        .set(TagFilter::new(args.tag, args.exclude_tag))
+        .expect("Failed to set TAG_FILTER OnceLock");
 
     // Get path from args
     let path = PathBuf::from_str(args.path.as_str())
@@ -205,10 +292,8 @@ fn main() {
     // TODO: I should probably be using some struct here that handles print/eprint rather
     //       than using std handles
     // Check if we were given a directory or a file
-    let results = if path_slice.is_dir() {
-        walk_directory(&mut stdout(), &mut stderr(), path_slice)
-    } else if path_slice.is_file() {
-        run_test_file(&mut stdout(), &mut stderr(), path_slice)
+    let results = if path_slice.is_dir() || path_slice.is_file() {
+        run_suite(&mut stdout(), &mut stderr(), path_slice)
     } else {
         Err(CLIError::new(
             "The given path was not a directory or file, when it must be one of those two"
@@ -235,14 +320,14 @@ fn main() {
 
 #[cfg(test)]
 mod main_tests {
+    use crate::frontend::SuiteFixtures;
     use crate::testing::util::test_writer::TestWriter;
     use crate::{run_test_file, walk_directory, TEST_NAME};
     use std::path::Path;
 
     fn initialize() {
-        if TEST_NAME.get().is_none() {
-            TEST_NAME.set(None).unwrap()
-        }
+        // The tests in the testing module may have already set this
+        let _ = TEST_NAME.set(None);
     }
 
     #[test]
@@ -250,10 +335,11 @@ mod main_tests {
         initialize();
         let mut std_out = TestWriter::new();
         let mut std_err = TestWriter::new();
+        let mut fixtures = SuiteFixtures::new();
 
         // Verify we get an error when running a test file with a file that doesn't exist
         let bad_path = Path::new("./src/testing/chs_files/idontexist.chs");
-        let res = run_test_file(&mut std_out, &mut std_err, bad_path);
+        let res = run_test_file(&mut std_out, &mut std_err, bad_path, &mut fixtures);
         assert!(
             res.is_err(),
             "Trying to run a file that does not exist should error"
@@ -261,7 +347,7 @@ mod main_tests {
 
         // Verify we get an error when running a non chs file
         let wrong_type_path = Path::new("./src/testing/chs_files/directory_tests/skipme.json");
-        let res = run_test_file(&mut std_out, &mut std_err, wrong_type_path);
+        let res = run_test_file(&mut std_out, &mut std_err, wrong_type_path, &mut fixtures);
         assert!(
             res.is_err(),
             "Trying to run a file that does not have a .chs extension should error"
@@ -269,7 +355,7 @@ mod main_tests {
 
         // Verify we get an error when running a test file with a directory
         let wrong_type_path = Path::new("./src/testing/chs_files/directory_tests/");
-        let res = run_test_file(&mut std_out, &mut std_err, wrong_type_path);
+        let res = run_test_file(&mut std_out, &mut std_err, wrong_type_path, &mut fixtures);
         assert!(
             res.is_err(),
             "Trying to run a test file but passing a directory should error"
@@ -277,7 +363,7 @@ mod main_tests {
 
         // Run a simple test
         let literal_test_path = Path::new("./src/testing/chs_files/simplest_test.chs");
-        let res = run_test_file(&mut std_out, &mut std_err, literal_test_path);
+        let res = run_test_file(&mut std_out, &mut std_err, literal_test_path, &mut fixtures);
         assert!(res.is_ok(), "Running a simple chs test should Ok");
     }
 
@@ -286,15 +372,16 @@ mod main_tests {
         initialize();
         let mut std_out = TestWriter::new();
         let mut std_err = TestWriter::new();
+        let mut fixtures = SuiteFixtures::new();
 
         // Try to walk directory on a file, which should fail
         let literal_test_path = Path::new("./src/testing/chs_files/simplest_test.chs");
-        let res = walk_directory(&mut std_out, &mut std_err, literal_test_path);
+        let res = walk_directory(&mut std_out, &mut std_err, literal_test_path, &mut fixtures);
         assert!(res.is_err(), "Passing walk_directory a file should Err");
 
         // Walk a directory, should only run .chs files
         let path = Path::new("./src/testing/chs_files/directory_tests/");
-        let res = walk_directory(&mut std_out, &mut std_err, path);
+        let res = walk_directory(&mut std_out, &mut std_err, path, &mut fixtures);
         assert!(res.is_ok(), "Passing walk_directory a directory should Ok");
         let result_count = res.unwrap();
         assert_eq!(result_count.success_count(), 3);
@@ -1,15 +1,19 @@
 pub(crate) mod util;
 
 #[cfg(test)]
+#[allow(clippy::module_inception, clippy::bool_assert_comparison)]
 mod testing {
     use crate::abstract_syntax_tree::ChimeraScriptAST;
     use crate::err_handle::{ChimeraRuntimeFailure, VarTypes};
-    use crate::frontend::{run_functions, TestResult};
+    use crate::frontend::{
+        run_fixture_teardown, run_function_by_name, run_functions, ResultCount, Status,
+        SuiteFixtures, TestResult,
+    };
     use crate::testing::util::{fake_client::FakeClient, test_writer::TestWriter};
-    use crate::CLIENT;
-    use std::ffi::OsStr;
+    use crate::util::tags::TagFilter;
+    use crate::{run_suite, CLIENT, TEST_NAME};
     use std::fs;
-    use std::path::Path;
+    use std::path::{Path, PathBuf};
     use std::sync::{Once, OnceLock};
 
     static INIT: Once = Once::new();
@@ -29,12 +33,17 @@ mod testing {
                 Ok(_) => (),
                 Err(_) => panic!("Failed to set fake client during test init"),
             }
+            // Run every test of a suite, this may have already been set by the tests in main.rs
+            let _ = TEST_NAME.set(None);
         });
     }
 
+    fn chs_file_path(filename: &str) -> PathBuf {
+        Path::new("./src/testing/chs_files").join(filename)
+    }
+
     fn read_cs_file(filename: &str) -> ChimeraScriptAST {
-        let full_filename = format!("./src/testing/chs_files/{}", filename);
-        let file_contents = fs::read_to_string(Path::new(&full_filename))
+        let file_contents = fs::read_to_string(chs_file_path(filename))
             .expect("Failed to read chs file when setting up test");
         ChimeraScriptAST::new(file_contents.as_str())
             .unwrap_or_else(|_e| panic!("Failed to parse a file into an AST"))
@@ -45,7 +54,48 @@ mod testing {
         let ast = read_cs_file(filename);
         let mut std_out = TestWriter::new();
         let mut std_err = TestWriter::new();
-        let test_results = run_functions(&mut std_out, &mut std_err, ast, OsStr::new(filename));
+        let test_results = run_functions(
+            &mut std_out,
+            &mut std_err,
+            ast,
+            &chs_file_path(filename),
+            &mut SuiteFixtures::new(),
+            None,
+        );
+        (test_results, std_out, std_err)
+    }
+
+    /// Run the tests of a file which are selected by name or by tags, like the `--name` and `--tag` arguments
+    fn selected_results_from_filename(
+        filename: &str,
+        tag_filter: Option<&TagFilter>,
+        name: Option<&str>,
+    ) -> (Vec<TestResult>, TestWriter, TestWriter) {
+        initialize();
+        let ast = read_cs_file(filename);
+        let mut std_out = TestWriter::new();
+        let mut std_err = TestWriter::new();
+        let path = chs_file_path(filename);
+        let mut fixtures = SuiteFixtures::new();
+        let test_results = match name {
+            Some(name) => run_function_by_name(
+                &mut std_out,
+                &mut std_err,
+                ast,
+                &path,
+                &mut fixtures,
+                tag_filter,
+                name,
+            ),
+            None => run_functions(
+                &mut std_out,
+                &mut std_err,
+                ast,
+                &path,
+                &mut fixtures,
+                tag_filter,
+            ),
+        };
         (test_results, std_out, std_err)
     }
 
@@ -96,14 +146,12 @@ mod testing {
                     should_fail_as.get_variant_name(),
                     failure.get_variant_name()
                 );
-                match failure {
-                    ChimeraRuntimeFailure::VarWrongType(_, got_var_type, _) => {
-                        match should_fail_as {
-                            ChimeraRuntimeFailure::VarWrongType(_, ref should_be_var_type, _) => assert_eq!(got_var_type, should_be_var_type, "Test case {} of file {} should fail with a {} error saying that the expected type should be a {} but it was {}", result.test_name(), filename, should_fail_as.get_variant_name(), should_be_var_type, got_var_type),
-                            _ => ()
-                        }
-                    },
-                    _ => ()
+                if let ChimeraRuntimeFailure::VarWrongType(_, got_var_type, _) = failure {
+                    if let ChimeraRuntimeFailure::VarWrongType(_, ref should_be_var_type, _) =
+                        should_fail_as
+                    {
+                        assert_eq!(got_var_type, should_be_var_type, "Test case {} of file {} should fail with a {} error saying that the expected type should be a {} but it was {}", result.test_name(), filename, should_fail_as.get_variant_name(), should_be_var_type, got_var_type)
+                    }
                 }
             }
             None => panic!(
@@ -143,7 +191,14 @@ mod testing {
         assert_eq!(ast.functions.len(), 1, "Should only get a single test for a test file which contains one test case but got multiple");
         let mut std_out = TestWriter::new();
         let mut std_err = TestWriter::new();
-        let res = run_functions(&mut std_out, &mut std_err, ast, OsStr::new(filename));
+        let res = run_functions(
+            &mut std_out,
+            &mut std_err,
+            ast,
+            &chs_file_path(filename),
+            &mut SuiteFixtures::new(),
+            None,
+        );
         assert_eq!(
             res.len(),
             1,
@@ -293,6 +348,46 @@ mod testing {
         )
     }
 
+    #[test]
+    /// Test that failed assertions print what their variables resolved to
+    fn assertion_messages() {
+        let filename = "assertion_messages.chs";
+        let (res, _std_out, std_err) = results_from_filename(filename);
+        let lines: Vec<String> = std_err
+            .str_lines()
+            .iter()
+            .map(|line| line.trim().to_owned())
+            .collect();
+        assert_eq!(res.len(), 2);
+        assert_eq!(
+            lines[0],
+            "FAILURE on line 1: Expected var 'name' to equal value 'foo'"
+        );
+        assert_eq!(lines[1], "var 'name' was \"bar\"");
+
+        // Both sides of the collection comparison are printed, followed by a diff
+        assert_eq!(
+            lines[2],
+            "FAILURE on line 2: Expected var 'res.body' to equal var 'other.body'"
+        );
+        assert_eq!(lines[3], "var 'res.body' was:");
+        assert_eq!(lines[4], "{");
+        assert_eq!(lines[5], "\"count\": 2,");
+        let diff_start = lines
+            .iter()
+            .position(|line| line == "Differences from the expected value:")
+            .expect("A failed collection comparison should print a diff");
+        assert_eq!(
+            lines[diff_start + 1..],
+            [
+                "extra key 'count'",
+                "missing key 'extra'",
+                "'name' was \"bar\" but expected \"foo\"",
+                "'path' was \"http://127.0.0.1:5000/items?name=bar&count=2\" but expected \"http://127.0.0.1:5000/items?name=foo&extra=1\"",
+            ]
+        );
+    }
+
     #[test]
     /// Test that test-cases can be nested
     fn nested_tests() {
@@ -665,6 +760,1067 @@ mod testing {
         );
     }
 
+    #[test]
+    /// Test that an EVENTUALLY block retries its statements until they pass or it gives up
+    fn eventually_block() {
+        let filename = "eventually.chs";
+        let (res, _std_out, std_err) = results_from_filename(filename);
+        let std_err_lines = std_err.str_lines();
+        assert_eq!(res.len(), 5);
+        assert_test_pass(
+            &res[0],
+            filename,
+            "when an EVENTUALLY block passes after a few attempts",
+        );
+        assert_test_pass(
+            &res[1],
+            filename,
+            "when making a request inside of an EVENTUALLY block",
+        );
+        assert_test_fail(
+            &res[2],
+            filename,
+            "when an EVENTUALLY block runs out of attempts",
+            ChimeraRuntimeFailure::TestFailure("".to_owned(), 0),
+        );
+        assert!(std_err_lines[0]
+            .trim()
+            .starts_with("FAILURE on line 1: EVENTUALLY block did not pass after 3 attempts"));
+        assert!(std_err_lines[0]
+            .trim()
+            .ends_with("last failure: Expected value '1' to equal value '2'"));
+        assert_test_fail(
+            &res[3],
+            filename,
+            "when an EVENTUALLY block runs out of time",
+            ChimeraRuntimeFailure::TestFailure("".to_owned(), 0),
+        );
+        assert_test_fail(
+            &res[4],
+            filename,
+            "when an EVENTUALLY block has an unknown option",
+            ChimeraRuntimeFailure::UnknownOption("".to_owned(), 0),
+        );
+    }
+
+    #[test]
+    /// Test the SLEEP statement and that it only accepts a valid number of seconds
+    fn sleep_command() {
+        let filename = "sleep.chs";
+        let (res, _std_out, std_err) = results_from_filename(filename);
+        let std_err_lines = std_err.str_lines();
+        assert_eq!(res.len(), 3);
+        assert_test_pass(&res[0], filename, "when sleeping for a number of seconds");
+        assert_test_fail(
+            &res[1],
+            filename,
+            "when sleeping for a string",
+            ChimeraRuntimeFailure::VarWrongType("".to_owned(), VarTypes::Number, 0),
+        );
+        assert_test_fail(
+            &res[2],
+            filename,
+            "when sleeping for a negative number of seconds",
+            ChimeraRuntimeFailure::InvalidDuration("".to_owned(), 0),
+        );
+        assert_eq!(
+            std_err_lines[1].trim(),
+            "ERROR on line 0: value '-1' is not a valid number of seconds"
+        );
+    }
+
+    #[test]
+    /// Test regex assertions and capturing groups with a MATCH expression
+    fn regex_matching() {
+        let filename = "matches.chs";
+        let (res, _std_out, std_err) = results_from_filename(filename);
+        let std_err_lines = std_err.str_lines();
+        assert_eq!(res.len(), 5);
+        assert_test_pass(&res[0], filename, "when asserting values match a regex");
+        assert_test_pass(&res[1], filename, "when capturing groups with MATCH");
+        assert_test_fail(
+            &res[2],
+            filename,
+            "when a value does not match a regex",
+            ChimeraRuntimeFailure::TestFailure("".to_owned(), 0),
+        );
+        assert_eq!(
+            std_err_lines[0].trim(),
+            "FAILURE on line 1: Expected var 'id' to match the pattern value '^org_'"
+        );
+        assert_test_fail(
+            &res[3],
+            filename,
+            "when using an invalid regex",
+            ChimeraRuntimeFailure::InvalidRegex("".to_owned(), 0),
+        );
+        assert_test_fail(
+            &res[4],
+            filename,
+            "when matching a regex against a non-string",
+            ChimeraRuntimeFailure::VarWrongType("".to_owned(), VarTypes::String, 0),
+        );
+    }
+
+    #[test]
+    /// Test validating data against a JSON Schema file
+    fn schema_assertion() {
+        let filename = "schema.chs";
+        let (res, _std_out, std_err) = results_from_filename(filename);
+        let lines: Vec<String> = std_err
+            .str_lines()
+            .iter()
+            .map(|line| line.trim().to_owned())
+            .collect();
+        assert_eq!(res.len(), 4);
+        assert_test_pass(&res[0], filename, "when data matches a schema");
+        assert_test_fail(
+            &res[1],
+            filename,
+            "when data has the wrong types for a schema",
+            ChimeraRuntimeFailure::TestFailure("".to_owned(), 0),
+        );
+        assert_eq!(
+            lines[0],
+            "FAILURE on line 1: Expected var 'res.body' to match the schema value 'schemas/user.json'"
+        );
+        // Each violation is listed by the pointer to the value which caused it
+        assert!(lines.contains(&"/age: \"old\" is not of type \"integer\"".to_owned()));
+        assert!(lines.contains(&"/name: 5 is not of type \"string\"".to_owned()));
+        assert_test_fail(
+            &res[2],
+            filename,
+            "when data is missing a required field",
+            ChimeraRuntimeFailure::TestFailure("".to_owned(), 0),
+        );
+        assert!(lines.contains(&"/: \"age\" is a required property".to_owned()));
+        assert_test_fail(
+            &res[3],
+            filename,
+            "when the schema file does not exist",
+            ChimeraRuntimeFailure::SchemaError("".to_owned(), 0),
+        );
+    }
+
+    #[test]
+    /// Test asserting the type of a value and getting it with TYPEOF
+    fn type_assertions() {
+        let filename = "types.chs";
+        let (res, _std_out, std_err) = results_from_filename(filename);
+        let std_err_lines = std_err.str_lines();
+        assert_eq!(res.len(), 4);
+        assert_test_pass(&res[0], filename, "when asserting the types of values");
+        assert_test_pass(
+            &res[1],
+            filename,
+            "when getting the type of a value with TYPEOF",
+        );
+        assert_test_fail(
+            &res[2],
+            filename,
+            "when a value is the wrong type",
+            ChimeraRuntimeFailure::TestFailure("".to_owned(), 0),
+        );
+        assert_eq!(
+            std_err_lines[0].trim(),
+            "FAILURE on line 1: Expected var 'id' to be of type value 'number'"
+        );
+        assert_eq!(std_err_lines[1].trim(), "Got type string");
+        assert_test_fail(
+            &res[3],
+            filename,
+            "when asserting an unknown type",
+            ChimeraRuntimeFailure::UnknownType("".to_owned(), 0),
+        );
+    }
+
+    #[test]
+    /// Test comparing numbers and lists of numbers within absolute and relative tolerances
+    fn approx_assertions() {
+        let filename = "approx.chs";
+        let (res, _std_out, std_err) = results_from_filename(filename);
+        let std_err_lines = std_err.str_lines();
+        assert_eq!(res.len(), 6);
+        assert_test_pass(
+            &res[0],
+            filename,
+            "when comparing numbers within a tolerance",
+        );
+        assert_test_pass(
+            &res[1],
+            filename,
+            "when comparing lists of numbers element-wise",
+        );
+        assert_test_fail(
+            &res[2],
+            filename,
+            "when a list member is outside the tolerance",
+            ChimeraRuntimeFailure::TestFailure("".to_owned(), 0),
+        );
+        assert_eq!(
+            std_err_lines[0].trim(),
+            "FAILURE on line 2: Expected var 'measured' to be within 0.1 of var 'expected'"
+        );
+        assert_eq!(
+            std_err_lines[1].trim(),
+            "Index 1: 2.5 differs from 2 by 0.5, more than the 0.1 allowed"
+        );
+        assert_test_fail(
+            &res[3],
+            filename,
+            "when comparing something which is not a number",
+            ChimeraRuntimeFailure::VarWrongType("".to_owned(), VarTypes::Number, 0),
+        );
+        assert_test_fail(
+            &res[4],
+            filename,
+            "when the tolerance is negative",
+            ChimeraRuntimeFailure::VarWrongType("".to_owned(), VarTypes::Tolerance, 0),
+        );
+        assert!(std_err_lines.iter().any(|line| line.trim()
+            == "ERROR on line 1: var 'tolerance' was expected to be of type Tolerance (a finite number, 0 or greater) but it was not"));
+        assert_test_fail(
+            &res[5],
+            filename,
+            "when the tolerance is infinite",
+            ChimeraRuntimeFailure::VarWrongType("".to_owned(), VarTypes::Tolerance, 0),
+        );
+    }
+
+    #[test]
+    /// Test that SUBSET ignores extra fields and can compare lists in any order
+    fn subset_assertions() {
+        let filename = "subset.chs";
+        let (res, _std_out, std_err) = results_from_filename(filename);
+        let std_err_lines = std_err.str_lines();
+        assert_eq!(res.len(), 3);
+        assert_test_pass(&res[0], filename, "when an object has extra fields");
+        assert_test_pass(&res[1], filename, "when comparing lists in any order");
+        assert_test_fail(
+            &res[2],
+            filename,
+            "when a field is missing or has the wrong value",
+            ChimeraRuntimeFailure::TestFailure("".to_owned(), 0),
+        );
+        assert_eq!(
+            std_err_lines[0].trim(),
+            "FAILURE on line 2: Expected var 'expected.body' to be a subset of var 'res.body'"
+        );
+        assert_eq!(std_err_lines[1].trim(), "missing key 'missing'");
+        assert_eq!(
+            std_err_lines[2].trim(),
+            "'name' was \"foo\" but expected \"bar\""
+        );
+    }
+
+    #[test]
+    /// Test that UNORDERED compares lists as multisets for EQUALS and CONTAINS
+    fn unordered_assertions() {
+        let filename = "unordered.chs";
+        let (res, _std_out, std_err) = results_from_filename(filename);
+        let std_err_lines = std_err.str_lines();
+        assert_eq!(res.len(), 3);
+        assert_test_pass(&res[0], filename, "when comparing lists in any order");
+        assert_test_pass(
+            &res[1],
+            filename,
+            "when checking a list contains a list in any order",
+        );
+        assert_test_fail(
+            &res[2],
+            filename,
+            "when lists have different members",
+            ChimeraRuntimeFailure::TestFailure("".to_owned(), 0),
+        );
+        assert_eq!(
+            std_err_lines[0].trim(),
+            "FAILURE on line 2: Expected var 'actual' to equal var 'expected' in any order"
+        );
+        let lines: Vec<&str> = std_err_lines.iter().map(|line| line.trim()).collect();
+        assert!(lines.contains(&"Differences from the expected value:"));
+        assert!(lines.contains(&"extra item 4"));
+        assert!(lines.contains(&"missing item 2"));
+    }
+
+    #[test]
+    /// Test comparing data against a stored snapshot with volatile fields ignored
+    fn snapshot_assertions() {
+        let filename = "snapshot.chs";
+        let (res, _std_out, std_err) = results_from_filename(filename);
+        let lines: Vec<String> = std_err
+            .str_lines()
+            .iter()
+            .map(|line| line.trim().to_owned())
+            .collect();
+        assert_eq!(res.len(), 3);
+        assert_test_pass(
+            &res[0],
+            filename,
+            "when data matches a snapshot apart from ignored fields",
+        );
+        assert_test_fail(
+            &res[1],
+            filename,
+            "when data does not match a snapshot",
+            ChimeraRuntimeFailure::TestFailure("".to_owned(), 0),
+        );
+        assert_eq!(
+            lines[0],
+            "FAILURE on line 1: Expected var 'res' to match the snapshot value 'created_item'"
+        );
+        assert_eq!(lines[1], "Differences from the snapshot:");
+        assert_eq!(lines[2], "'body.name' was \"bar\" but expected \"foo\"");
+        assert_eq!(lines[3], "missing key 'body.tags'");
+        assert_test_fail(
+            &res[2],
+            filename,
+            "when a snapshot does not exist",
+            ChimeraRuntimeFailure::SnapshotError("".to_owned(), 0),
+        );
+    }
+
+    #[test]
+    /// Test matching status codes by class or list, and asserting on response headers
+    fn status_and_header_assertions() {
+        let filename = "status_and_headers.chs";
+        let (res, _std_out, std_err) = results_from_filename(filename);
+        let lines: Vec<String> = std_err
+            .str_lines()
+            .iter()
+            .map(|line| line.trim().to_owned())
+            .collect();
+        assert_eq!(res.len(), 6);
+        assert_test_pass(&res[0], filename, "when matching status classes and lists");
+        assert_test_pass(&res[1], filename, "when asserting on response headers");
+        assert_test_fail(
+            &res[2],
+            filename,
+            "when a status is not in a list",
+            ChimeraRuntimeFailure::TestFailure("".to_owned(), 0),
+        );
+        assert_eq!(
+            lines[0],
+            "FAILURE on line 1: Expected var 'res' to have a status code of list [200, 4xx]"
+        );
+        assert_eq!(lines[1], "Got status code 201");
+        assert_test_fail(
+            &res[3],
+            filename,
+            "when a header is missing",
+            ChimeraRuntimeFailure::TestFailure("".to_owned(), 0),
+        );
+        assert!(lines.contains(&"The response has no 'etag' header".to_owned()));
+        assert_test_fail(
+            &res[4],
+            filename,
+            "when a status class is not valid",
+            ChimeraRuntimeFailure::VarWrongType("".to_owned(), VarTypes::StatusCode, 0),
+        );
+        assert_test_fail(
+            &res[5],
+            filename,
+            "when a status code is not a whole number",
+            ChimeraRuntimeFailure::VarWrongType("".to_owned(), VarTypes::StatusCode, 0),
+        );
+    }
+
+    #[test]
+    /// Test that failed EXPECT statements are all recorded without stopping the test
+    fn soft_assertions() {
+        let filename = "expect.chs";
+        let (res, std_out, std_err) = results_from_filename(filename);
+        let out_lines: Vec<&str> = std_out.str_lines().iter().map(|l| l.trim()).collect();
+        let err_lines: Vec<&str> = std_err.str_lines().iter().map(|l| l.trim()).collect();
+        assert_eq!(res.len(), 7);
+        assert_test_fail(
+            &res[0],
+            filename,
+            "when EXPECT statements fail",
+            ChimeraRuntimeFailure::TestFailure("".to_owned(), 0),
+        );
+        assert_eq!(res[0].failures().len(), 2);
+        assert!(out_lines.contains(&"reached the end"));
+        assert_eq!(
+            err_lines[0],
+            "FAILURE on line 1: Expected var 'res.body.name' to equal value 'bar'"
+        );
+        assert!(err_lines.contains(
+            &"FAILURE on line 2: Expected var 'res' to have a status code of value '200'"
+        ));
+
+        assert_test_fail(
+            &res[1],
+            filename,
+            "when an ASSERT fails after an EXPECT",
+            ChimeraRuntimeFailure::TestFailure("".to_owned(), 0),
+        );
+        assert_eq!(res[1].failures().len(), 2);
+        assert!(!out_lines.contains(&"not reached"));
+
+        assert_test_pass(&res[2], filename, "when every EXPECT passes");
+        assert_test_fail(
+            &res[3],
+            filename,
+            "when an EXPECT errors",
+            ChimeraRuntimeFailure::VarNotFound("".to_owned(), 0),
+        );
+        assert_test_pass(
+            &res[4],
+            filename,
+            "when an expected failure has a failed EXPECT",
+        );
+
+        assert_test_fail(
+            &res[5],
+            filename,
+            "when an EVENTUALLY block gives up on an EXPECT",
+            ChimeraRuntimeFailure::TestFailure("".to_owned(), 0),
+        );
+        assert_eq!(res[5].failures().len(), 1);
+        assert!(out_lines.contains(&"after eventually"));
+
+        // An error after failed EXPECT statements is counted as an error, but keeps the failures
+        assert_test_fail(
+            &res[6],
+            filename,
+            "when an error follows failed EXPECT statements",
+            ChimeraRuntimeFailure::VarNotFound("".to_owned(), 0),
+        );
+        assert_eq!(res[6].failures().len(), 2);
+        let result_count = res[6].get_result_counts();
+        assert_eq!(result_count.error_count(), 1);
+        assert_eq!(result_count.failure_count(), 0);
+    }
+
+    #[test]
+    /// Test that ASSERT ERROR blocks pass only when the named error is raised
+    fn assert_error_blocks() {
+        let filename = "assert_error.chs";
+        let (res, std_out, std_err) = results_from_filename(filename);
+        let out_lines: Vec<&str> = std_out.str_lines().iter().map(|l| l.trim()).collect();
+        let err_lines: Vec<&str> = std_err.str_lines().iter().map(|l| l.trim()).collect();
+        assert_eq!(res.len(), 5);
+        assert_test_pass(&res[0], filename, "when the expected error is raised");
+        assert!(out_lines.contains(&"after the block"));
+        assert_test_pass(&res[1], filename, "when an ASSERT fails inside the block");
+        assert_test_pass(&res[2], filename, "when an EXPECT fails inside the block");
+        assert!(!out_lines.contains(&"not reached"));
+
+        assert_test_fail(
+            &res[3],
+            filename,
+            "when the block raises no error",
+            ChimeraRuntimeFailure::TestFailure("".to_owned(), 0),
+        );
+        assert!(err_lines.contains(
+            &"FAILURE on line 0: Expected the block to raise a VarNotFound error, but it finished without one"
+        ));
+        assert_test_fail(
+            &res[4],
+            filename,
+            "when the block raises a different error",
+            ChimeraRuntimeFailure::TestFailure("".to_owned(), 0),
+        );
+        assert!(err_lines.contains(
+            &"FAILURE on line 0: Expected the block to raise a OutOfBounds error, but it raised a VarNotFound error"
+        ));
+        assert!(err_lines.contains(&"ERROR on line 0: var 'missing' was accessed but is not set"));
+    }
+
+    #[test]
+    /// Test that SKIP and FAIL statements end the test with the matching status
+    fn skip_and_fail_statements() {
+        let filename = "skip_and_fail.chs";
+        let (res, std_out, std_err) = results_from_filename(filename);
+        let out_lines: Vec<&str> = std_out.str_lines().iter().map(|l| l.trim()).collect();
+        let err_lines: Vec<&str> = std_err.str_lines().iter().map(|l| l.trim()).collect();
+        assert_eq!(res.len(), 8);
+        assert!(
+            matches!(res[0].status(), Status::Skip(reason) if reason == "the feature is disabled")
+        );
+        assert!(out_lines.contains(&"before the skip"));
+        assert!(!out_lines.contains(&"not reached"));
+        assert!(out_lines
+            .iter()
+            .any(|l| l.starts_with("FINISHED TEST - skipped -")
+                && l.ends_with("SKIP - the feature is disabled")));
+        assert!(matches!(res[1].status(), Status::Skip(reason) if reason == "search is disabled"));
+
+        assert_test_fail(
+            &res[2],
+            filename,
+            "when a FAIL statement is reached",
+            ChimeraRuntimeFailure::TestFailure("".to_owned(), 0),
+        );
+        assert!(err_lines.contains(&"FAILURE on line 0: this should never run"));
+        assert_test_pass(&res[3], filename, "when an expected failure uses FAIL");
+        assert_test_fail(
+            &res[4],
+            filename,
+            "when a SKIP follows a failed EXPECT",
+            ChimeraRuntimeFailure::TestFailure("".to_owned(), 0),
+        );
+        assert!(
+            matches!(res[5].status(), Status::Skip(_)),
+            "Expected a SKIP inside of an EVENTUALLY block to skip the test"
+        );
+        assert!(
+            matches!(res[6].status(), Status::ExpectedFailure),
+            "Expected an expected failure which fails an EXPECT and then skips to be an expected failure"
+        );
+        assert!(
+            matches!(res[7].status(), Status::Skip(reason) if reason == "from inside an ASSERT ERROR block"),
+            "Expected a SKIP inside of an ASSERT ERROR block to skip the test, not be raised as an error"
+        );
+
+        let result_count = ResultCount::from_test_results(res);
+        assert_eq!(result_count.skip_count(), 4);
+        assert_eq!(result_count.success_count(), 2);
+    }
+
+    #[test]
+    /// Test that skip decorators skip a case and every case nested inside of it
+    fn skip_decorators() {
+        let filename = "skip_decorators.chs";
+        let (res, std_out, std_err) = results_from_filename(filename);
+        let out_lines: Vec<&str> = std_out.str_lines().iter().map(|l| l.trim()).collect();
+        assert_eq!(res.len(), 4);
+        assert!(
+            matches!(res[0].status(), Status::Skip(reason) if reason == "skipped by the [skip] decorator")
+        );
+        assert!(matches!(res[1].status(), Status::Skip(reason) if reason == "not deployed yet"));
+        assert_eq!(res[1].subtest_results.len(), 1);
+        assert!(
+            matches!(res[1].subtest_results[0].status(), Status::Skip(reason) if reason == "not deployed yet")
+        );
+        assert!(out_lines.contains(&"SKIPPED TEST - nested-in-skipped - not deployed yet"));
+        assert!(matches!(res[2].status(), Status::Skip(reason) if reason == "PATH is set"));
+        assert_test_pass(&res[3], filename, "when a skip-if variable is not set");
+        assert!(out_lines.contains(&"ran"));
+        assert!(std_err.str_lines().is_empty());
+
+        let result_count = ResultCount::from_test_results(res);
+        assert_eq!(result_count.skip_count(), 4);
+    }
+
+    #[test]
+    /// Test that teardown blocks run in reverse order however a test ends
+    fn teardown_blocks() {
+        let filename = "teardown.chs";
+        let (res, std_out, std_err) = results_from_filename(filename);
+        let out_lines: Vec<&str> = std_out.str_lines().iter().map(|l| l.trim()).collect();
+        let err_lines: Vec<&str> = std_err.str_lines().iter().map(|l| l.trim()).collect();
+        assert_eq!(res.len(), 3);
+        assert_test_pass(&res[0], filename, "when running teardown");
+        let position = |line: &str| out_lines.iter().position(|l| *l == line).unwrap();
+        assert!(position("test body") < position("second teardown"));
+        assert!(position("second teardown") < position("first teardown"));
+
+        assert_test_fail(
+            &res[1],
+            filename,
+            "when teardown fails",
+            ChimeraRuntimeFailure::TestFailure("".to_owned(), 0),
+        );
+        assert!(out_lines.contains(&"rest of teardown"));
+        assert!(err_lines.iter().any(|l| l.starts_with("FAILURE")));
+
+        assert_test_fail(
+            &res[2],
+            filename,
+            "when the test errors",
+            ChimeraRuntimeFailure::VarNotFound("".to_owned(), 0),
+        );
+        assert!(out_lines.contains(&"teardown after error"));
+    }
+
+    #[test]
+    /// Test that a timeout decorator ends a test which runs too long, and that teardown still runs
+    fn timeout_decorator() {
+        let filename = "timeout.chs";
+        let (res, std_out, std_err) = results_from_filename(filename);
+        let out_lines: Vec<&str> = std_out.str_lines().iter().map(|l| l.trim()).collect();
+        let err_lines: Vec<&str> = std_err.str_lines().iter().map(|l| l.trim()).collect();
+        assert_eq!(res.len(), 4);
+        assert_test_fail(
+            &res[0],
+            filename,
+            "when a statement runs past the timeout",
+            ChimeraRuntimeFailure::Timeout(0.0, 0),
+        );
+        assert!(err_lines
+            .contains(&"ERROR on line 1: The test did not finish within its timeout of 0.2s"));
+        assert!(out_lines.contains(&"teardown ran"));
+        assert!(!out_lines.contains(&"not reached"));
+        assert_test_pass(&res[1], filename, "when a test finishes within its timeout");
+
+        assert_test_fail(
+            &res[2],
+            filename,
+            "when a nested case runs past its parent's timeout",
+            ChimeraRuntimeFailure::Timeout(0.0, 0),
+        );
+        assert_test_fail(
+            &res[2].subtest_results[0],
+            filename,
+            "when it runs past its parent's timeout",
+            ChimeraRuntimeFailure::Timeout(0.0, 0),
+        );
+        assert_test_fail(
+            &res[3],
+            filename,
+            "when an EVENTUALLY block is still retrying at the timeout",
+            ChimeraRuntimeFailure::Timeout(0.0, 0),
+        );
+    }
+
+    #[test]
+    /// Test that a retry decorator re-runs a failing test with fresh variables and teardown
+    fn retry_decorator() {
+        let filename = "retry.chs";
+        let (res, std_out, _) = results_from_filename(filename);
+        let out_lines: Vec<&str> = std_out.str_lines().iter().map(|l| l.trim()).collect();
+        assert_eq!(res.len(), 4);
+        assert_test_pass(&res[0], filename, "when it passes on a retry");
+        assert_eq!(res[0].attempts(), 2);
+        assert!(res[0].is_flaky());
+        assert_eq!(
+            out_lines.iter().filter(|l| **l == "flaky teardown").count(),
+            2
+        );
+        assert!(out_lines.contains(&"RETRYING TEST - flaky - attempt 1 of 3 was a FAILURE"));
+
+        assert_test_fail(
+            &res[1],
+            filename,
+            "when every attempt fails",
+            ChimeraRuntimeFailure::TestFailure("".to_owned(), 0),
+        );
+        assert_eq!(res[1].attempts(), 3);
+        assert!(!res[1].is_flaky());
+        assert_test_pass(&res[2], filename, "when it passes the first time");
+        assert_eq!(res[2].attempts(), 1);
+        assert!(!res[2].is_flaky());
+
+        assert_test_pass(
+            &res[3],
+            filename,
+            "when a retried nested case changes its parent's variables",
+        );
+        assert!(res[3].subtest_results[0].is_flaky());
+
+        let result_count = ResultCount::from_test_results(res);
+        assert_eq!(result_count.flaky_count(), 2);
+    }
+
+    #[test]
+    /// Test that a parametrized test runs once for each row of a PARAMS table or file
+    fn params() {
+        let filename = "params.chs";
+        let (res, std_out, std_err) = results_from_filename(filename);
+        let out_lines: Vec<&str> = std_out.str_lines().iter().map(|l| l.trim()).collect();
+        let err_lines: Vec<&str> = std_err.str_lines().iter().map(|l| l.trim()).collect();
+        let names: Vec<&str> = res.iter().map(|r| r.test_name()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "equal-counts[0]",
+                "equal-counts[1]",
+                "equal-counts[2]",
+                "login[0]",
+                "login[1]",
+                "items[0]",
+                "items[1]",
+                "missing-file",
+                "without-params"
+            ]
+        );
+        assert_test_pass(&res[0], filename, "with the first row of a table");
+        assert_test_pass(&res[1], filename, "with the second row of a table");
+        assert_test_fail(
+            &res[2],
+            filename,
+            "with a row which should fail",
+            ChimeraRuntimeFailure::TestFailure("".to_owned(), 0),
+        );
+        assert_test_pass(&res[3], filename, "with a row from a CSV file");
+        assert_test_pass(&res[4], filename, "with a quoted row from a CSV file");
+        assert_test_pass(&res[5], filename, "with a row from a JSON file");
+        assert_test_pass(&res[6], filename, "with a row from a JSON file");
+        assert!(out_lines.contains(&"foo has 2 tags"));
+        assert!(out_lines.contains(&"bar has 0 tags"));
+
+        assert_test_fail(
+            &res[7],
+            filename,
+            "when its PARAMS file does not exist",
+            ChimeraRuntimeFailure::ParamsError("".to_owned(), 0),
+        );
+        // A file which fails to load is reported on the line its PARAMS are declared on
+        assert!(err_lines.iter().any(|line| line
+            .starts_with("ERROR on line 32: Failed to read the file")
+            && line.ends_with("missing.csv' for PARAMS missing")));
+        assert_test_pass(&res[8], filename, "without params");
+    }
+
+    #[test]
+    /// Test that hooks run around the tests of a file, which see the variables they set
+    fn hooks() {
+        let filename = "hooks.chs";
+        let (res, std_out, _) = results_from_filename(filename);
+        let out_lines: Vec<&str> = std_out.str_lines().iter().map(|l| l.trim()).collect();
+        let names: Vec<&str> = res.iter().map(|r| r.test_name()).collect();
+        assert_eq!(names, vec!["first", "second"]);
+        assert_test_pass(&res[0], filename, "with variables from the hooks");
+        assert_test_pass(
+            &res[1],
+            filename,
+            "without variables from the test before it",
+        );
+        assert_eq!(
+            out_lines
+                .iter()
+                .filter(|line| line.starts_with("STARTING TEST"))
+                .collect::<Vec<_>>(),
+            vec![
+                &"STARTING TEST - create-user",
+                &"STARTING TEST - start-order",
+                &"STARTING TEST - first",
+                &"STARTING TEST - end-order",
+                &"STARTING TEST - start-order",
+                &"STARTING TEST - second",
+                &"STARTING TEST - end-order",
+                &"STARTING TEST - delete-user",
+            ]
+        );
+        assert!(out_lines.contains(&"after-each saw 2 items"));
+        assert!(out_lines.contains(&"after-each saw 1 items"));
+        assert!(out_lines.contains(&"deleted hooks with 0 items"));
+    }
+
+    #[test]
+    /// Test that a failing [before-all] hook errors every test without running it
+    fn hooks_failing_setup() {
+        let filename = "hooks_failing_setup.chs";
+        let (res, std_out, std_err) = results_from_filename(filename);
+        let out_lines: Vec<&str> = std_out.str_lines().iter().map(|l| l.trim()).collect();
+        let err_lines: Vec<&str> = std_err.str_lines().iter().map(|l| l.trim()).collect();
+        assert_eq!(res.len(), 2);
+        for result in &res {
+            assert_test_fail(
+                result,
+                filename,
+                "when its setup failed",
+                ChimeraRuntimeFailure::HookFailure("".to_owned()),
+            );
+        }
+        assert!(!out_lines.contains(&"not reached"));
+        assert!(
+            err_lines.contains(&"ERROR: The [before-all] case create-user did not pass: FAILURE")
+        );
+        // The [after-all] hook still runs to clean up whatever was set up
+        assert!(out_lines.contains(&"deleted hooks"));
+    }
+
+    #[test]
+    /// Test that a failing [after-each] hook errors a test which passed, and a failing [after-all] hook is
+    /// reported on its own
+    fn hooks_failing_each() {
+        let filename = "hooks_failing_each.chs";
+        let (res, _, _) = results_from_filename(filename);
+        let names: Vec<&str> = res.iter().map(|r| r.test_name()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "counted[0]",
+                "counted[1]",
+                "failing[0]",
+                "failing[1]",
+                "after-all"
+            ]
+        );
+        assert_test_pass(&res[0], filename, "when its hooks passed");
+        let hook_failure = || ChimeraRuntimeFailure::HookFailure("".to_owned());
+        assert_test_fail(
+            &res[1],
+            filename,
+            "when its [after-each] hook failed",
+            hook_failure(),
+        );
+        // A test which already failed keeps its own failure
+        for result in &res[2..4] {
+            assert_test_fail(
+                result,
+                filename,
+                "with a failing assertion",
+                ChimeraRuntimeFailure::TestFailure("".to_owned(), 0),
+            );
+        }
+        assert_test_fail(
+            &res[4],
+            filename,
+            "when the [after-all] hook failed",
+            hook_failure(),
+        );
+    }
+
+    #[test]
+    /// Test that a suite's fixtures are set up once for every file, and torn down after the last one. A
+    /// fixtures file in a subdirectory of the suite is skipped
+    fn suite_fixtures() {
+        initialize();
+        let mut std_out = TestWriter::new();
+        let mut std_err = TestWriter::new();
+        let path = chs_file_path("fixture_tests");
+        let result_count = run_suite(&mut std_out, &mut std_err, &path).unwrap();
+        assert_eq!(result_count.success_count(), 3);
+        assert_eq!(result_count.failure_count(), 0);
+        assert_eq!(result_count.error_count(), 0);
+        let out_lines: Vec<String> = std_out
+            .str_lines()
+            .iter()
+            .map(|l| l.trim().to_owned())
+            .collect();
+        let fixture_runs = out_lines
+            .iter()
+            .filter(|line| line.starts_with("STARTING FIXTURE"))
+            .count();
+        assert_eq!(fixture_runs, 1, "The fixture should only run once");
+        assert_eq!(out_lines[out_lines.len() - 1], "logged out fixture-user");
+
+        // Running a single file of the suite still sets up the suite's fixtures
+        let mut std_out = TestWriter::new();
+        let path = chs_file_path("fixture_tests/orders.chs");
+        let result_count = run_suite(&mut std_out, &mut std_err, &path).unwrap();
+        assert_eq!(result_count.success_count(), 1);
+    }
+
+    #[test]
+    /// Test that every test of a suite errors when one of its fixtures fails
+    fn suite_fixtures_failing() {
+        initialize();
+        let mut std_out = TestWriter::new();
+        let mut std_err = TestWriter::new();
+        let path = chs_file_path("failing_fixture_tests");
+        let result_count = run_suite(&mut std_out, &mut std_err, &path).unwrap();
+        assert_eq!(result_count.success_count(), 0);
+        assert_eq!(result_count.error_count(), 2);
+        let err_lines: Vec<String> = std_err
+            .str_lines()
+            .iter()
+            .map(|l| l.trim().to_owned())
+            .collect();
+        assert!(
+            err_lines.contains(&"ERROR: The [fixture] case login did not pass: FAILURE".to_owned())
+        );
+    }
+
+    #[test]
+    /// Test that a suite's fixtures are neither set up nor torn down when none of its tests are selected
+    fn suite_fixtures_not_selected() {
+        initialize();
+        let fixtures_path = chs_file_path("fixture_tests/fixtures.chs");
+        let fixtures_ast = read_cs_file("fixture_tests/fixtures.chs");
+        let path = chs_file_path("fixture_tests/orders.chs");
+        let mut std_out = TestWriter::new();
+        let mut std_err = TestWriter::new();
+        let mut fixtures = SuiteFixtures::from_file(&fixtures_ast, &fixtures_path);
+        let results = run_function_by_name(
+            &mut std_out,
+            &mut std_err,
+            read_cs_file("fixture_tests/orders.chs"),
+            &path,
+            &mut fixtures,
+            None,
+            "does-not-exist",
+        );
+        assert_eq!(results.len(), 0);
+        let teardown_results = run_fixture_teardown(&mut std_out, &mut std_err, &mut fixtures);
+        assert_eq!(teardown_results.len(), 0);
+        let out_lines: Vec<String> = std_out
+            .str_lines()
+            .iter()
+            .map(|l| l.trim().to_owned())
+            .collect();
+        assert!(
+            !out_lines
+                .iter()
+                .any(|line| line.starts_with("STARTING FIXTURE")
+                    || line == "TEARING DOWN FIXTURES"),
+            "The fixtures should not run when no test is selected"
+        );
+
+        // Selecting a test sets up the fixtures and tears them down once the suite has finished
+        let mut std_out = TestWriter::new();
+        let mut fixtures = SuiteFixtures::from_file(&fixtures_ast, &fixtures_path);
+        let results = run_function_by_name(
+            &mut std_out,
+            &mut std_err,
+            read_cs_file("fixture_tests/orders.chs"),
+            &path,
+            &mut fixtures,
+            None,
+            "create-order",
+        );
+        assert_eq!(results.len(), 1);
+        assert_test_pass(&results[0], "fixture_tests/orders.chs", "with fixtures");
+        run_fixture_teardown(&mut std_out, &mut std_err, &mut fixtures);
+        let out_lines: Vec<String> = std_out
+            .str_lines()
+            .iter()
+            .map(|l| l.trim().to_owned())
+            .collect();
+        assert!(out_lines.contains(&"STARTING FIXTURE - login".to_owned()));
+        assert_eq!(out_lines[out_lines.len() - 1], "logged out fixture-user");
+    }
+
+    #[test]
+    /// Test that a case runs after the cases it depends on, and is skipped when one did not pass
+    fn depends_on() {
+        let filename = "depends_on.chs";
+        let (res, std_out, _) = results_from_filename(filename);
+        let out_lines: Vec<&str> = std_out.str_lines().iter().map(|l| l.trim()).collect();
+        let names: Vec<&str> = res.iter().map(|r| r.test_name()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "create-user",
+                "read-user",
+                "broken",
+                "after-broken",
+                "after-skipped",
+                "after-both"
+            ]
+        );
+        assert_test_pass(&res[0], filename, "without dependencies");
+        assert_test_pass(&res[1], filename, "after its dependency passed");
+        assert!(matches!(
+            res[3].status(),
+            Status::Skip(reason) if reason == "depends on broken, which did not pass"
+        ));
+        assert!(matches!(
+            res[4].status(),
+            Status::Skip(reason) if reason == "depends on after-broken, which did not pass"
+        ));
+        assert_test_pass(&res[5], filename, "after all of its dependencies passed");
+        assert!(!out_lines.contains(&"not reached"));
+        assert!(out_lines
+            .contains(&"SKIPPED TEST - after-broken - depends on broken, which did not pass"));
+    }
+
+    #[test]
+    /// Test that a test selected by name or by tag also runs the tests it depends on
+    fn depends_on_selected() {
+        let filename = "depends_on.chs";
+        let (res, _, _) = selected_results_from_filename(filename, None, Some("after-both"));
+        let names: Vec<&str> = res.iter().map(|r| r.test_name()).collect();
+        assert_eq!(names, vec!["create-user", "read-user", "after-both"]);
+        for result in &res {
+            assert_test_pass(result, filename, "when selected by name");
+        }
+
+        let tag_filter = TagFilter::new(vec!["smoke".to_owned()], Vec::new());
+        let (res, _, _) = selected_results_from_filename(filename, Some(&tag_filter), None);
+        let names: Vec<&str> = res.iter().map(|r| r.test_name()).collect();
+        assert_eq!(names, vec!["create-user", "read-user"]);
+        for result in &res {
+            assert_test_pass(result, filename, "when selected by tag");
+        }
+
+        // A dependency with an excluded tag is skipped rather than run, along with the tests which need it
+        let tag_filter = TagFilter::new(vec!["smoke".to_owned()], vec!["setup".to_owned()]);
+        let (res, std_out, _) = selected_results_from_filename(filename, Some(&tag_filter), None);
+        let names: Vec<&str> = res.iter().map(|r| r.test_name()).collect();
+        assert_eq!(names, vec!["create-user", "read-user"]);
+        assert!(matches!(
+            res[0].status(),
+            Status::Skip(reason) if reason == "excluded by --exclude-tag, but depended on"
+        ));
+        assert!(matches!(
+            res[1].status(),
+            Status::Skip(reason) if reason == "depends on create-user, which did not pass"
+        ));
+        assert!(!std_out
+            .str_lines()
+            .iter()
+            .any(|l| l.trim() == "creating user"));
+        let result_count = ResultCount::from_test_results(res);
+        assert_eq!(result_count.skip_count(), 2);
+    }
+
+    #[test]
+    /// Test that --tag and --exclude-tag select which tests of a file run, while its hooks still run
+    /// around the tests that remain
+    fn tag_filter() {
+        let filename = "tags.chs";
+        let names_and_counts = |tag_filter: &TagFilter| {
+            let (res, std_out, _) =
+                selected_results_from_filename(filename, Some(tag_filter), None);
+            let out_lines: Vec<String> = std_out
+                .str_lines()
+                .iter()
+                .map(|l| l.trim().to_owned())
+                .collect();
+            for result in res.iter() {
+                assert_test_pass(result, filename, "with a tag filter");
+            }
+            let names: Vec<String> = res.iter().map(|r| r.test_name().to_owned()).collect();
+            let nested: Vec<String> = res
+                .iter()
+                .flat_map(|r| r.subtest_results.iter())
+                .map(|r| r.test_name().to_owned())
+                .collect();
+            let result_count = ResultCount::from_test_results(res);
+            (names, nested, result_count, out_lines)
+        };
+
+        let smoke = TagFilter::new(vec!["smoke".to_owned()], Vec::new());
+        let (names, nested, result_count, out_lines) = names_and_counts(&smoke);
+        assert_eq!(names, vec!["smoke", "smoke-and-slow"]);
+        assert_eq!(nested, vec!["smoke-slow-child", "smoke-child"]);
+        assert_eq!(result_count.success_count(), 4);
+        assert_eq!(result_count.skip_count(), 0);
+        assert!(out_lines.contains(&"created tags".to_owned()));
+        assert!(out_lines.contains(&"deleted tags".to_owned()));
+        assert_eq!(
+            out_lines
+                .iter()
+                .filter(|line| line.as_str() == "before-each")
+                .count(),
+            2,
+            "The [before-each] hook should run once for each selected test"
+        );
+        assert_eq!(
+            out_lines
+                .iter()
+                .filter(|line| line.as_str() == "after-each")
+                .count(),
+            2,
+            "The [after-each] hook should run once for each selected test"
+        );
+
+        let smoke_not_slow = TagFilter::new(vec!["smoke".to_owned()], vec!["slow".to_owned()]);
+        let (names, nested, result_count, out_lines) = names_and_counts(&smoke_not_slow);
+        assert_eq!(names, vec!["smoke"]);
+        assert_eq!(nested, vec!["smoke-child"]);
+        assert_eq!(result_count.success_count(), 2);
+        assert!(out_lines.contains(&"created tags".to_owned()));
+        assert!(out_lines.contains(&"deleted tags".to_owned()));
+
+        let not_slow = TagFilter::new(Vec::new(), vec!["slow".to_owned()]);
+        let (names, _, result_count, _) = names_and_counts(&not_slow);
+        assert_eq!(names, vec!["smoke", "untagged"]);
+        assert_eq!(result_count.success_count(), 3);
+
+        // No hook runs when the filter selects nothing in the file
+        let unused = TagFilter::new(vec!["unused".to_owned()], Vec::new());
+        let (names, _, result_count, out_lines) = names_and_counts(&unused);
+        assert!(names.is_empty());
+        assert_eq!(result_count.success_count(), 0);
+        assert!(!out_lines.contains(&"created tags".to_owned()));
+        assert!(!out_lines.contains(&"deleted tags".to_owned()));
+    }
+
     // TODO: Test for get_result_counts. Test something with multiple outer cases, nested tests, passes, errors, and failures
     //       Make sure some nested cases are reached and others are not (they are nested after a failure of parent)
 }
@@ -4,17 +4,23 @@ use crate::frontend::Context;
 use crate::literal::{Collection, Data, DataKind, Literal, NumberKind};
 use crate::util::client::WebClient;
 use std::collections::HashMap;
+use std::sync::Mutex;
 
 #[derive(Debug)]
 pub struct FakeClient {
     domain: String,
+    // How many times each /counter/ path has been requested, so a test can change between runs
+    counters: Mutex<HashMap<String, u64>>,
 }
 
 impl FakeClient {
     #[allow(dead_code)] // Used in test
     pub fn new(s: &str) -> Self {
         let domain = s.to_owned();
-        Self { domain }
+        Self {
+            domain,
+            counters: Mutex::new(HashMap::new()),
+        }
     }
 }
 
@@ -25,7 +31,7 @@ impl WebClient for FakeClient {
     fn make_request(
         &self,
         context: &Context,
-        http_command: HttpCommand,
+        http_command: &HttpCommand,
     ) -> Result<DataKind, ChimeraRuntimeFailure> {
         let mut response_obj: HashMap<String, Data> = HashMap::new();
         response_obj.insert(
@@ -51,17 +57,41 @@ impl WebClient for FakeClient {
             let value = query_param.rhs.resolve(context)?;
             query_params.insert(key, value);
         }
-        let raw_headers = &http_command.resolve_header(context)?;
+        // Resolve headers to make sure they are valid, but echo back their values as they were
+        // written so a header set to a number is returned as a number
+        http_command.resolve_header(context)?;
         let mut headers: HashMap<String, Data> = HashMap::new();
-        for (key, value) in raw_headers.iter() {
-            let deserializable_value = format!("\"{}\"", value.to_str().unwrap());
-            let data = Data::new(serde_json::from_slice(deserializable_value.as_bytes()).unwrap());
-            headers.insert(key.to_string(), data);
+        // Response headers are the request headers with lowercase names, as strings
+        let mut response_headers: HashMap<String, Data> = HashMap::new();
+        response_headers.insert(
+            "content-type".to_owned(),
+            Data::from_literal(Literal::String("application/json".to_owned())),
+        );
+        for header in &http_command.headers {
+            let value = header.rhs.resolve(context)?;
+            let value_string = value.borrow()?.to_string();
+            response_headers.insert(
+                header.lhs.to_ascii_lowercase(),
+                Data::from_literal(Literal::String(value_string)),
+            );
+            headers.insert(header.lhs.clone(), value);
         }
 
         // Construct a response struct out of the request params
         let mut body_data: HashMap<String, Data> = HashMap::new();
-        let resolved_path = http_command.resolve_path(context)?;
+        let resolved_path = http_command.resolve_path(self.get_domain(), context)?;
+        if resolved_path.contains("/counter/") {
+            let mut counters = self
+                .counters
+                .lock()
+                .expect("FakeClient counters were poisoned");
+            let count = counters.entry(resolved_path.clone()).or_insert(0);
+            *count += 1;
+            body_data.insert(
+                "count".to_owned(),
+                Data::from_literal(Literal::Number(NumberKind::U64(*count))),
+            );
+        }
         body_data.insert(
             "path".to_owned(),
             Data::new(DataKind::Literal(Literal::String(resolved_path))),
@@ -72,6 +102,10 @@ impl WebClient for FakeClient {
             body_data.extend(headers);
         }
 
+        response_obj.insert(
+            "headers".to_owned(),
+            Data::new(DataKind::Collection(Collection::Object(response_headers))),
+        );
         response_obj.insert(
             "body".to_owned(),
             Data::new(DataKind::Collection(Collection::Object(body_data))),
@@ -1,28 +1,39 @@
-use crate::abstract_syntax_tree::{HTTPVerb, HttpCommand};
+use crate::abstract_syntax_tree::HttpCommand;
 use crate::err_handle::ChimeraRuntimeFailure;
 use crate::frontend::Context;
 use crate::literal::{Collection, Data, DataKind, Literal, NumberKind};
-use reqwest;
+use crate::util::config::RetryPolicy;
+use crate::util::transport::Transport;
 use std::collections::HashMap;
+use std::thread;
 
 pub trait WebClient {
     fn get_domain(&self) -> &str;
     fn make_request(
         &self,
         context: &Context,
-        http_command: HttpCommand,
+        http_command: &HttpCommand,
     ) -> Result<DataKind, ChimeraRuntimeFailure>;
 }
 
 #[derive(Debug)]
 pub struct RealClient {
     domain: String,
-    client: reqwest::blocking::Client,
+    transport: Box<dyn Transport + Send + Sync>,
+    retry_policy: RetryPolicy,
 }
 
 impl RealClient {
-    pub fn new(domain: String, client: reqwest::blocking::Client) -> Self {
-        Self { domain, client }
+    pub fn new(
+        domain: String,
+        transport: Box<dyn Transport + Send + Sync>,
+        retry_policy: RetryPolicy,
+    ) -> Self {
+        Self {
+            domain,
+            transport,
+            retry_policy,
+        }
     }
 }
 
@@ -33,47 +44,59 @@ impl WebClient for RealClient {
     fn make_request(
         &self,
         context: &Context,
-        http_command: HttpCommand,
+        http_command: &HttpCommand,
     ) -> Result<DataKind, ChimeraRuntimeFailure> {
-        let resolved_path = http_command.resolve_path(context)?;
+        let resolved_path = http_command.resolve_path(self.get_domain(), context)?;
         let body_map = http_command.resolve_body(context)?;
         let headers = http_command.resolve_header(context)?;
+        let options = http_command.resolve_request_options(context, &self.retry_policy)?;
+        let retry_policy = &options.retry_policy;
+        // Each attempt is given the request's timeout, cut short by the test's deadline if sooner
+        let timeout = match (options.timeout, context.time_remaining()) {
+            (Some(timeout), Some(remaining)) => Some(timeout.min(remaining)),
+            (timeout, remaining) => timeout.or(remaining),
+        };
 
-        // Make the web request
-        let res = match http_command.verb {
-            HTTPVerb::Get => self
-                .client
-                .get(resolved_path.as_str())
-                .headers(headers)
-                .send(),
-            HTTPVerb::Delete => self
-                .client
-                .delete(resolved_path.as_str())
-                .headers(headers)
-                .send(),
-            HTTPVerb::Post => self
-                .client
-                .post(resolved_path.as_str())
-                .json(&body_map)
-                .headers(headers)
-                .send(),
-            HTTPVerb::Put => self
-                .client
-                .put(resolved_path.as_str())
-                .json(&body_map)
-                .headers(headers)
-                .send(),
+        // Make the web request, retrying it if it fails in a way the retry policy allows
+        let mut attempt: u32 = 0;
+        let res = loop {
+            match self.transport.send(
+                &http_command.verb,
+                resolved_path.as_str(),
+                headers.clone(),
+                &body_map,
+                timeout,
+            ) {
+                Err(e) if attempt < retry_policy.count && retry_policy.should_retry(e) => {
+                    attempt += 1;
+                    let delay = retry_policy.delay_before(attempt);
+                    context.log_verbose(format!(
+                        "RETRYING REQUEST - {} - attempt {} of {} after a {} failure, waiting {}s",
+                        resolved_path,
+                        attempt,
+                        retry_policy.count,
+                        e,
+                        delay.as_secs_f64()
+                    ));
+                    thread::sleep(context.cap_to_deadline(delay));
+                }
+                res => break res,
+            }
         };
         match res {
             Ok(response) => {
-                // Have to store the status here as reading the body consumes the response
-                let status_code: u64 = response.status().as_u16().into();
-                let body: DataKind = response.json().unwrap_or(DataKind::Literal(Literal::Null));
+                let status_code: u64 = response.status_code.into();
+                let body: DataKind = serde_json::from_slice(&response.body)
+                    .unwrap_or(DataKind::Literal(Literal::Null));
                 let mut http_response_obj: HashMap<String, Data> = HashMap::new();
                 http_response_obj.insert(
                     "status_code".to_owned(),
                     Data::from_literal(Literal::Number(NumberKind::U64(status_code))),
                 );
+                http_response_obj.insert(
+                    "headers".to_owned(),
+                    Data::new(headers_to_object(response.headers)),
+                );
                 http_response_obj.insert("body".to_owned(), Data::new(body));
                 Ok(DataKind::Collection(Collection::Object(http_response_obj)))
             }
@@ -84,3 +107,283 @@ impl WebClient for RealClient {
         }
     }
 }
+
+/// Collect response headers into an object keyed by their lowercase names. A header which was
+/// sent more than once has its values joined with a comma, the same as if it was sent once
+fn headers_to_object(headers: Vec<(String, String)>) -> DataKind {
+    let mut joined: HashMap<String, String> = HashMap::new();
+    for (name, value) in headers {
+        joined
+            .entry(name.to_ascii_lowercase())
+            .and_modify(|existing| {
+                existing.push_str(", ");
+                existing.push_str(&value);
+            })
+            .or_insert(value);
+    }
+    let object = joined
+        .into_iter()
+        .map(|(name, value)| (name, Data::from_literal(Literal::String(value))))
+        .collect();
+    DataKind::Collection(Collection::Object(object))
+}
+
+#[cfg(test)]
+mod client_tests {
+    use super::*;
+    use crate::abstract_syntax_tree::{HTTPVerb, KeyValuePair, Value};
+    use crate::frontend::Deadline;
+    use crate::util::transport::{RawResponse, TransportError};
+    use crate::variable_map::VariableMap;
+    use reqwest::header::HeaderMap;
+    use std::ops::Deref;
+    use std::path::Path;
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// A transport which fails with `error` a set number of times before it starts succeeding
+    #[derive(Debug)]
+    struct FlakyTransport {
+        failures_left: Mutex<u32>,
+        error: TransportError,
+        // The timeout each request was sent with
+        timeouts: Mutex<Vec<Option<Duration>>>,
+    }
+
+    impl Transport for FlakyTransport {
+        fn send(
+            &self,
+            _verb: &HTTPVerb,
+            _url: &str,
+            _headers: HeaderMap,
+            _body: &HashMap<String, String>,
+            timeout: Option<Duration>,
+        ) -> Result<RawResponse, TransportError> {
+            self.timeouts.lock().unwrap().push(timeout);
+            let mut failures_left = self.failures_left.lock().unwrap();
+            if *failures_left > 0 {
+                *failures_left -= 1;
+                return Err(self.error);
+            }
+            Ok(RawResponse {
+                status_code: 200,
+                headers: vec![
+                    ("content-type".to_owned(), "application/json".to_owned()),
+                    ("set-cookie".to_owned(), "a=1".to_owned()),
+                    ("Set-Cookie".to_owned(), "b=2".to_owned()),
+                ],
+                body: b"{\"ok\": true, \"id\": \"5\", \"count\": 5}".to_vec(),
+            })
+        }
+    }
+
+    // Lets a test keep a handle to a transport it gave to a client
+    impl Transport for Arc<FlakyTransport> {
+        fn send(
+            &self,
+            verb: &HTTPVerb,
+            url: &str,
+            headers: HeaderMap,
+            body: &HashMap<String, String>,
+            timeout: Option<Duration>,
+        ) -> Result<RawResponse, TransportError> {
+            self.as_ref().send(verb, url, headers, body, timeout)
+        }
+    }
+
+    fn flaky_transport(failures: u32, error: TransportError) -> FlakyTransport {
+        FlakyTransport {
+            failures_left: Mutex::new(failures),
+            error,
+            timeouts: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn flaky_client(failures: u32, error: TransportError, retry_count: u32) -> RealClient {
+        let transport = flaky_transport(failures, error);
+        let retry_policy = RetryPolicy {
+            count: retry_count,
+            backoff: 0.0,
+            ..RetryPolicy::default()
+        };
+        RealClient::new(
+            "http://127.0.0.1:5000".to_owned(),
+            Box::new(transport),
+            retry_policy,
+        )
+    }
+
+    fn get_command(key_val_pairs: Vec<KeyValuePair>) -> HttpCommand {
+        HttpCommand {
+            verb: HTTPVerb::Get,
+            path: vec![Value::from_str("/test_resource").unwrap()],
+            query_params: Vec::new(),
+            http_assignments: Vec::new(),
+            headers: Vec::new(),
+            key_val_pairs,
+        }
+    }
+
+    #[test]
+    /// Test that connection failures are retried and each retry is logged
+    fn retry_connection_failure() {
+        let mut variable_map = VariableMap::new();
+        let context = Context::new(&mut variable_map, Path::new("."));
+        let client = flaky_client(2, TransportError::Connect, 2);
+        let res = client.make_request(&context, &get_command(Vec::new()));
+        assert!(
+            res.is_ok(),
+            "A request should succeed once its retries get a response"
+        );
+        assert_eq!(context.take_verbose_log().len(), 2);
+
+        // Running out of retries is a failed request
+        let client = flaky_client(3, TransportError::Connect, 2);
+        let res = client.make_request(&context, &get_command(Vec::new()));
+        assert_eq!(
+            res.err(),
+            Some(ChimeraRuntimeFailure::WebRequestFailure("".to_owned(), 0))
+        );
+    }
+
+    #[test]
+    /// Test that only the kinds of failures in the retry policy are retried
+    fn retry_only_retryable_failures() {
+        let mut variable_map = VariableMap::new();
+        let context = Context::new(&mut variable_map, Path::new("."));
+        let client = flaky_client(1, TransportError::Timeout, 3);
+        let res = client.make_request(&context, &get_command(Vec::new()));
+        assert!(res.is_err(), "Timeouts should not be retried by default");
+        assert_eq!(context.take_verbose_log().len(), 0);
+    }
+
+    #[test]
+    /// Test that the retry policy can be changed for a single request with key value pairs
+    fn retry_key_value_pairs() {
+        let mut variable_map = VariableMap::new();
+        let context = Context::new(&mut variable_map, Path::new("."));
+        let client = flaky_client(2, TransportError::Timeout, 0);
+        let key_val_pairs = vec![
+            KeyValuePair {
+                key: "retries".to_owned(),
+                value: Value::Literal(Literal::Number(NumberKind::U64(2))),
+            },
+            KeyValuePair {
+                key: "retry_on".to_owned(),
+                value: Value::Literal(Literal::String("connect, timeout".to_owned())),
+            },
+            KeyValuePair {
+                key: "backoff".to_owned(),
+                value: Value::Literal(Literal::Number(NumberKind::U64(0))),
+            },
+        ];
+        let res = client.make_request(&context, &get_command(key_val_pairs));
+        assert!(res.is_ok());
+
+        let unknown_option = vec![KeyValuePair {
+            key: "foo".to_owned(),
+            value: Value::Literal(Literal::Number(NumberKind::U64(2))),
+        }];
+        let res = client.make_request(&context, &get_command(unknown_option));
+        assert_eq!(
+            res.err(),
+            Some(ChimeraRuntimeFailure::UnknownOption("".to_owned(), 0))
+        );
+    }
+
+    #[test]
+    /// Test that strings in a response body are kept as strings even when they look like numbers
+    fn response_body_types() {
+        let mut variable_map = VariableMap::new();
+        let context = Context::new(&mut variable_map, Path::new("."));
+        let client = flaky_client(0, TransportError::Connect, 0);
+        let res = client
+            .make_request(&context, &get_command(Vec::new()))
+            .expect("A request with no failures should succeed");
+        let body = match res {
+            DataKind::Collection(Collection::Object(response)) => response["body"].clone(),
+            _ => panic!("A response should be an object"),
+        };
+        let body = body.borrow().unwrap();
+        let DataKind::Collection(Collection::Object(body)) = body.deref() else {
+            panic!("The response body should be an object")
+        };
+        assert_eq!(body["id"].borrow().unwrap().type_name(), "string");
+        assert_eq!(body["count"].borrow().unwrap().type_name(), "number");
+    }
+
+    #[test]
+    /// Test that response headers are keyed by their lowercase names, with repeats joined together
+    fn response_headers() {
+        let mut variable_map = VariableMap::new();
+        let context = Context::new(&mut variable_map, Path::new("."));
+        let client = flaky_client(0, TransportError::Connect, 0);
+        let res = client
+            .make_request(&context, &get_command(Vec::new()))
+            .expect("A request with no failures should succeed");
+        let headers = match res {
+            DataKind::Collection(Collection::Object(response)) => response["headers"].clone(),
+            _ => panic!("A response should be an object"),
+        };
+        let headers = headers.borrow().unwrap();
+        let DataKind::Collection(Collection::Object(headers)) = headers.deref() else {
+            panic!("The response headers should be an object")
+        };
+        assert_eq!(
+            headers["content-type"].borrow().unwrap().deref(),
+            &DataKind::Literal(Literal::String("application/json".to_owned()))
+        );
+        assert_eq!(
+            headers["set-cookie"].borrow().unwrap().deref(),
+            &DataKind::Literal(Literal::String("a=1, b=2".to_owned()))
+        );
+    }
+
+    #[test]
+    /// Test that a request's timeout is given to every attempt, and is cut short by the test deadline
+    fn request_timeout() {
+        let transport = Arc::new(flaky_transport(1, TransportError::Timeout));
+        let retry_policy = RetryPolicy {
+            backoff: 0.0,
+            ..RetryPolicy::default()
+        };
+        let client = RealClient::new(
+            "http://127.0.0.1:5000".to_owned(),
+            Box::new(transport.clone()),
+            retry_policy,
+        );
+        let mut variable_map = VariableMap::new();
+        let mut context = Context::new(&mut variable_map, Path::new("."));
+        let key_val_pairs = |seconds: f64| {
+            vec![
+                KeyValuePair {
+                    key: "timeout".to_owned(),
+                    value: Value::Literal(Literal::Number(NumberKind::F64(seconds))),
+                },
+                KeyValuePair {
+                    key: "retries".to_owned(),
+                    value: Value::Literal(Literal::Number(NumberKind::U64(1))),
+                },
+                KeyValuePair {
+                    key: "retry_on".to_owned(),
+                    value: Value::Literal(Literal::String("timeout".to_owned())),
+                },
+            ]
+        };
+        let res = client.make_request(&context, &get_command(key_val_pairs(1.5)));
+        assert!(res.is_ok());
+        assert_eq!(
+            *transport.timeouts.lock().unwrap(),
+            vec![Some(Duration::from_secs_f64(1.5)); 2]
+        );
+
+        transport.timeouts.lock().unwrap().clear();
+        context.set_deadline(Some(Deadline::new(Duration::from_secs(1))));
+        let res = client.make_request(&context, &get_command(key_val_pairs(60.0)));
+        assert!(res.is_ok());
+        let timeouts = transport.timeouts.lock().unwrap();
+        assert_eq!(timeouts.len(), 1);
+        assert!(timeouts[0].unwrap() <= Duration::from_secs(1));
+    }
+}
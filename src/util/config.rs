@@ -1,26 +1,319 @@
+#[cfg(unix)]
+use crate::util::transport::UnixSocketTransport;
+use crate::util::transport::{ReqwestTransport, Transport, TransportError};
 use serde::Deserialize;
 use std::fs;
+use std::net::IpAddr;
 use std::ops::Add;
+use std::time::Duration;
+
+/// The domain used for requests to a socket target. There is no network address to speak of, but
+/// requests still need a well formed url so their path and query can be resolved
+const SOCKET_DOMAIN: &str = "http://localhost";
 
 #[derive(Deserialize, Debug)]
 pub struct Config {
-    pub ip: String,
-    pub port: u16,
+    pub ip: Option<String>,
+    pub port: Option<u16>,
+    /// Path to a Unix domain socket the target listens on, used instead of `ip` and `port`
+    pub socket: Option<String>,
+    /// Proxy that requests to the target are routed through, e.g. "http://127.0.0.1:8080" or
+    /// "socks5://proxy.corp:1080"
+    pub proxy: Option<String>,
+    /// Comma separated list of hosts which should bypass the proxy
+    pub no_proxy: Option<String>,
+    /// How requests which fail to get a response are retried
+    #[serde(default)]
+    pub retry: RetryPolicy,
+}
+
+/// Controls retrying requests which failed at the connection level, before any response was
+/// received. A request which gets a response, even an error status code, is never retried
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// How many times a failed request is retried, 0 disables retrying
+    pub count: u32,
+    /// Seconds to wait before the first retry, each following retry waits twice as long
+    pub backoff: f64,
+    /// Which kinds of failures are retried
+    pub retry_on: Vec<TransportError>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            backoff: 0.5,
+            retry_on: vec![TransportError::Connect],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// How long to wait before making the given retry, where the first retry is attempt 1
+    pub fn delay_before(&self, attempt: u32) -> Duration {
+        let exponent = i32::try_from(attempt.saturating_sub(1)).unwrap_or(i32::MAX);
+        let delay = self.backoff * 2f64.powi(exponent);
+        // A delay too long to represent waits as long as it can, rather than panicking mid-suite
+        match Duration::try_from_secs_f64(delay) {
+            Ok(delay) => delay,
+            Err(_) if delay.is_nan() || delay <= 0.0 => Duration::ZERO,
+            Err(_) => Duration::MAX,
+        }
+    }
+    pub fn should_retry(&self, error: TransportError) -> bool {
+        self.retry_on.contains(&error)
+    }
 }
 
 impl Config {
     pub fn from_path_str(path_str: &str) -> Result<Self, String> {
         match fs::read_to_string(path_str) {
-            Ok(file_contents) => match toml::from_str::<Self>(&file_contents) {
-                Ok(res) => Ok(res),
-                Err(e) => Err(format!("Failed to parse config file, {}", e.message())),
-            },
+            Ok(file_contents) => Self::from_toml_str(&file_contents),
             Err(_) => Err(format!("Failed to read config file with path {}", path_str)),
         }
     }
+    /// Parse and validate the contents of a config file
+    pub fn from_toml_str(file_contents: &str) -> Result<Self, String> {
+        let config = match toml::from_str::<Self>(file_contents) {
+            Ok(res) => res,
+            Err(e) => return Err(format!("Failed to parse config file, {}", e.message())),
+        };
+        if !config.retry.backoff.is_finite() || config.retry.backoff < 0.0 {
+            return Err(
+                "Config retry backoff must be a number of seconds, 0 or greater".to_owned(),
+            );
+        }
+        if config.socket.is_none() && (config.ip.is_none() || config.port.is_none()) {
+            return Err(
+                "Config file must set either an ip and port or a socket for the target".to_owned(),
+            );
+        }
+        Ok(config)
+    }
     pub fn get_target_address(&self) -> String {
-        let mut cloned = self.ip.clone();
+        if self.socket.is_some() {
+            return SOCKET_DOMAIN.to_owned();
+        }
+        let mut cloned = self
+            .ip
+            .clone()
+            .expect("Config was validated to have an ip when it has no socket");
         cloned.push(':');
-        cloned.add(&self.port.to_string())
+        cloned.add(
+            &self
+                .port
+                .expect("Config was validated to have a port when it has no socket")
+                .to_string(),
+        )
+    }
+    /// Replace the proxy settings from the config file with ones passed in from the CLI
+    pub fn override_proxy(&mut self, proxy: Option<String>, no_proxy: Option<String>) {
+        if proxy.is_some() {
+            self.proxy = proxy;
+        }
+        if no_proxy.is_some() {
+            self.no_proxy = no_proxy;
+        }
+    }
+    /// Build the transport which requests to the target are sent over
+    pub fn build_transport(&self) -> Result<Box<dyn Transport + Send + Sync>, String> {
+        // Requests to a socket never go over the network, so there is nothing to proxy
+        if self.socket.is_some() && (self.proxy.is_some() || self.no_proxy.is_some()) {
+            return Err("A proxy cannot be used with a socket target".to_owned());
+        }
+        match &self.socket {
+            #[cfg(unix)]
+            Some(socket_path) => Ok(Box::new(UnixSocketTransport::new(socket_path.into()))),
+            #[cfg(not(unix))]
+            Some(_) => Err("Socket targets are only supported on Unix platforms".to_owned()),
+            None => Ok(Box::new(ReqwestTransport::new(self.build_http_client()?))),
+        }
+    }
+    fn build_http_client(&self) -> Result<reqwest::blocking::Client, String> {
+        let mut builder = reqwest::blocking::Client::builder();
+        if let Some(proxy_url) = &self.proxy {
+            let proxy = match reqwest::Proxy::all(proxy_url.as_str()) {
+                Ok(proxy) => proxy,
+                Err(_) => return Err(format!("Proxy '{}' is not a valid proxy url", proxy_url)),
+            };
+            let no_proxy = match &self.no_proxy {
+                Some(hosts) => Some(parse_no_proxy(hosts)?),
+                None => None,
+            };
+            builder = builder.proxy(proxy.no_proxy(no_proxy));
+        }
+        builder
+            .build()
+            .map_err(|e| format!("Failed to build the web client, {}", e))
+    }
+}
+
+/// Parse a comma separated list of hosts which bypass the proxy. reqwest treats anything which is not
+/// an ip as a domain name, so a typo would otherwise be silently ignored rather than reported
+fn parse_no_proxy(hosts: &str) -> Result<reqwest::NoProxy, String> {
+    for host in hosts.split(',').map(str::trim) {
+        if !is_valid_no_proxy_host(host) {
+            return Err(format!(
+                "No proxy host '{}' is not a valid domain, ip or ip range",
+                host
+            ));
+        }
+    }
+    Ok(reqwest::NoProxy::from_string(hosts).expect("No proxy hosts were checked to not be empty"))
+}
+
+fn is_valid_no_proxy_host(host: &str) -> bool {
+    if host == "*" {
+        return true;
+    }
+    // An ip range such as 10.0.0.0/8
+    let (address, prefix_len) = match host.split_once('/') {
+        Some((address, prefix_len)) => (address, Some(prefix_len)),
+        None => (host, None),
+    };
+    if let Ok(ip) = address.parse::<IpAddr>() {
+        let max_prefix_len = if ip.is_ipv4() { 32 } else { 128 };
+        return prefix_len.is_none_or(|prefix_len| {
+            prefix_len
+                .parse::<u8>()
+                .is_ok_and(|prefix_len| prefix_len <= max_prefix_len)
+        });
+    }
+    // A leading dot matches only subdomains of the domain that follows it
+    let domain = host.strip_prefix('.').unwrap_or(host);
+    prefix_len.is_none()
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+
+    #[test]
+    /// Test that retry delays double, and a delay too long to represent is capped rather than panicking
+    fn retry_delays() {
+        let policy = RetryPolicy {
+            count: u32::MAX,
+            backoff: 0.5,
+            retry_on: Vec::new(),
+        };
+        assert_eq!(policy.delay_before(1), Duration::from_millis(500));
+        assert_eq!(policy.delay_before(3), Duration::from_secs(2));
+        assert_eq!(policy.delay_before(5000), Duration::MAX);
+        assert_eq!(policy.delay_before(u32::MAX), Duration::MAX);
+        let no_backoff = RetryPolicy {
+            backoff: 0.0,
+            ..policy
+        };
+        assert_eq!(no_backoff.delay_before(u32::MAX), Duration::ZERO);
+    }
+
+    #[test]
+    /// Test that a retry backoff which is negative or not finite fails to load
+    fn invalid_backoff() {
+        let target = "ip = \"127.0.0.1\"\nport = 5000\n";
+        assert!(Config::from_toml_str(&format!("{}[retry]\nbackoff = 1.5\n", target)).is_ok());
+        for backoff in ["-1.0", "inf", "nan"] {
+            let config = format!("{}[retry]\nbackoff = {}\n", target, backoff);
+            assert!(
+                Config::from_toml_str(&config).is_err(),
+                "Expected a backoff of {} to fail to load",
+                backoff
+            );
+        }
+    }
+
+    #[test]
+    /// Test that a socket target cannot also be given a proxy, from the config file or the CLI
+    fn socket_with_proxy() {
+        let socket = "socket = \"/tmp/chimerascript.sock\"\n";
+        let mut config = Config::from_toml_str(socket).unwrap();
+        config.override_proxy(Some("http://127.0.0.1:8080".to_owned()), None);
+        assert!(config.build_transport().is_err());
+        let with_proxy = format!("{}proxy = \"http://127.0.0.1:8080\"\n", socket);
+        let config = Config::from_toml_str(&with_proxy).unwrap();
+        assert!(config.build_transport().is_err());
+    }
+
+    #[test]
+    /// Test that a proxy and its no proxy hosts are accepted, and an invalid one is reported
+    fn proxy() {
+        let target = "ip = \"127.0.0.1\"\nport = 5000\n";
+        for proxy in ["http://127.0.0.1:8080", "socks5://127.0.0.1:1080"] {
+            let config = format!("{}proxy = \"{}\"\n", target, proxy);
+            let config = Config::from_toml_str(&config).unwrap();
+            assert_eq!(config.proxy.as_deref(), Some(proxy));
+            if let Err(e) = config.build_transport() {
+                panic!("Expected proxy {} to be valid, {}", proxy, e);
+            }
+        }
+        let config = format!("{}proxy = \"not a proxy\"\n", target);
+        assert!(Config::from_toml_str(&config)
+            .unwrap()
+            .build_transport()
+            .is_err());
+    }
+
+    #[test]
+    /// Test that no proxy hosts may be domains, ips or ip ranges, and anything else fails to build
+    fn no_proxy() {
+        let target = "ip = \"127.0.0.1\"\nport = 5000\nproxy = \"http://127.0.0.1:8080\"\n";
+        for hosts in [
+            "localhost",
+            "*",
+            "example.com, .internal.corp",
+            "10.0.0.1,10.0.0.0/8",
+            "::1, fd00::/8",
+        ] {
+            let config = format!("{}no_proxy = \"{}\"\n", target, hosts);
+            let config = Config::from_toml_str(&config).unwrap();
+            assert!(
+                config.build_transport().is_ok(),
+                "Expected no proxy hosts {} to be valid",
+                hosts
+            );
+        }
+        for hosts in [
+            "",
+            "example.com,",
+            "http://example.com",
+            "example..com",
+            "10.0.0.0/33",
+            "example.com/8",
+        ] {
+            let config = format!("{}no_proxy = \"{}\"\n", target, hosts);
+            let config = Config::from_toml_str(&config).unwrap();
+            assert!(
+                config.build_transport().is_err(),
+                "Expected no proxy hosts {} to be invalid",
+                hosts
+            );
+        }
+    }
+
+    #[test]
+    /// Test that proxy settings from the CLI replace those from the config file, and unset ones are kept
+    fn override_proxy() {
+        let config = "ip = \"127.0.0.1\"\nport = 5000\nproxy = \"http://127.0.0.1:8080\"\nno_proxy = \"localhost\"\n";
+        let mut config = Config::from_toml_str(config).unwrap();
+        config.override_proxy(None, None);
+        assert_eq!(config.proxy.as_deref(), Some("http://127.0.0.1:8080"));
+        assert_eq!(config.no_proxy.as_deref(), Some("localhost"));
+        config.override_proxy(Some("socks5://proxy.corp:1080".to_owned()), None);
+        assert_eq!(config.proxy.as_deref(), Some("socks5://proxy.corp:1080"));
+        assert_eq!(config.no_proxy.as_deref(), Some("localhost"));
+        config.override_proxy(None, Some("example.com".to_owned()));
+        assert_eq!(config.proxy.as_deref(), Some("socks5://proxy.corp:1080"));
+        assert_eq!(config.no_proxy.as_deref(), Some("example.com"));
+        // An invalid no proxy from the CLI is reported the same as one from the config file
+        config.override_proxy(None, Some("example.com,".to_owned()));
+        assert!(config.build_transport().is_err());
     }
 }
@@ -1,3 +1,8 @@
 pub mod client;
 pub mod config;
+pub mod params;
+pub mod pretty;
+pub mod snapshot;
+pub mod tags;
 pub mod timer;
+pub mod transport;
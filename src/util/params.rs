@@ -0,0 +1,217 @@
+use crate::abstract_syntax_tree::{ParamsBlock, ParamsSource};
+use crate::err_handle::ChimeraRuntimeFailure;
+use crate::literal::{Collection, Data, DataKind, Literal};
+use std::fs;
+use std::ops::Deref;
+use std::path::Path;
+
+/// One row of params, the name and value of each variable it binds
+pub type ParamsRow = Vec<(String, Data)>;
+
+/// Get the rows of a PARAMS block, loading them from its file when it has one. A file is read
+/// relative to the directory of the test file
+pub fn load_rows(
+    params: &ParamsBlock,
+    test_directory: &Path,
+) -> Result<Vec<ParamsRow>, ChimeraRuntimeFailure> {
+    let params_error = |reason: String| ChimeraRuntimeFailure::ParamsError(reason, params.line);
+    let file_name = match &params.source {
+        ParamsSource::Table { columns, rows } => {
+            return Ok(rows
+                .iter()
+                .map(|row| {
+                    columns
+                        .iter()
+                        .cloned()
+                        .zip(
+                            row.iter()
+                                .map(|literal| Data::from_literal(literal.clone())),
+                        )
+                        .collect()
+                })
+                .collect())
+        }
+        ParamsSource::File(file_name) => file_name,
+    };
+    let path = test_directory.join(file_name);
+    let contents = fs::read_to_string(&path).map_err(|_| {
+        params_error(format!(
+            "Failed to read the file '{}' for PARAMS {}",
+            path.display(),
+            params.name
+        ))
+    })?;
+    let rows = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => json_rows(&contents),
+        Some("csv") => csv_rows(&contents),
+        _ => Err("only .csv and .json files are supported".to_owned()),
+    };
+    rows.map_err(|reason| {
+        params_error(format!(
+            "Failed to load PARAMS {} from '{}', {}",
+            params.name,
+            path.display(),
+            reason
+        ))
+    })
+}
+
+/// A JSON file is a list of objects, each key of an object is a variable
+fn json_rows(contents: &str) -> Result<Vec<ParamsRow>, String> {
+    let json: DataKind = serde_json::from_str(contents).map_err(|e| e.to_string())?;
+    let list = match json {
+        DataKind::Collection(Collection::List(list)) => list,
+        _ => return Err("expected a list of objects".to_owned()),
+    };
+    let mut rows: Vec<ParamsRow> = Vec::new();
+    for (index, item) in list.iter().enumerate() {
+        let mut row: ParamsRow = match item.borrow().map_err(|e| e.to_string())?.deref() {
+            DataKind::Collection(Collection::Object(object)) => object
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+            _ => return Err(format!("item {} is not an object", index)),
+        };
+        // Objects are unordered, sort them so variables are bound in the same order every run
+        row.sort_by(|(a, _), (b, _)| a.cmp(b));
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// A CSV file has a header line of variable names, and then a line for each row. A quoted value is
+/// a string, anything else is read as a number, boolean, or null when it is one
+fn csv_rows(contents: &str) -> Result<Vec<ParamsRow>, String> {
+    let mut lines = contents.lines().filter(|line| !line.trim().is_empty());
+    let columns: Vec<String> = match lines.next() {
+        Some(header) => split_csv_line(header)?
+            .into_iter()
+            .map(|(column, _)| column)
+            .collect(),
+        None => return Err("the file is empty".to_owned()),
+    };
+    let mut rows: Vec<ParamsRow> = Vec::new();
+    for (index, line) in lines.enumerate() {
+        let fields = split_csv_line(line)?;
+        if fields.len() != columns.len() {
+            return Err(format!(
+                "there are {} columns but row {} has {} values",
+                columns.len(),
+                index,
+                fields.len()
+            ));
+        }
+        let row = columns
+            .iter()
+            .cloned()
+            .zip(
+                fields
+                    .into_iter()
+                    .map(|(field, quoted)| csv_value(field, quoted)),
+            )
+            .collect();
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+// Split a line on its commas, returning each trimmed field and whether it was quoted. Inside of
+// quotes a comma is part of the field and "" is a quote
+fn split_csv_line(line: &str) -> Result<Vec<(String, bool)>, String> {
+    let mut fields: Vec<(String, bool)> = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        let mut field = String::new();
+        while chars.peek().is_some_and(|c| *c == ' ' || *c == '\t') {
+            chars.next();
+        }
+        let quoted = chars.peek() == Some(&'"');
+        if quoted {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => field.push(c),
+                    None => return Err(format!("a quote is not closed in the line '{}'", line)),
+                }
+            }
+            while chars.peek().is_some_and(|c| *c != ',') {
+                chars.next();
+            }
+        } else {
+            while let Some(c) = chars.peek().filter(|c| **c != ',') {
+                field.push(*c);
+                chars.next();
+            }
+            field = field.trim_end().to_owned();
+        }
+        fields.push((field, quoted));
+        if chars.next().is_none() {
+            return Ok(fields);
+        }
+    }
+}
+
+fn csv_value(field: String, quoted: bool) -> Data {
+    if !quoted {
+        if let Ok(literal @ DataKind::Literal(_)) = serde_json::from_str::<DataKind>(&field) {
+            return Data::new(literal);
+        }
+    }
+    Data::from_literal(Literal::String(field))
+}
+
+#[cfg(test)]
+mod params_tests {
+    use super::*;
+    use crate::literal::NumberKind;
+
+    fn literal_at(rows: &[ParamsRow], row: usize, column: usize) -> Literal {
+        match rows[row][column].1.borrow().unwrap().deref() {
+            DataKind::Literal(literal) => literal.clone(),
+            DataKind::Collection(_) => panic!("Expected a literal param"),
+        }
+    }
+
+    #[test]
+    /// Test that CSV values are typed unless they are quoted, and quoted values can hold commas
+    fn csv_params() {
+        let rows = csv_rows("name, count, active\nfoo, 5, true\n\"bar, baz\", \"5\", null\n")
+            .expect("Failed to parse valid CSV");
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0][0].0, "name");
+        assert_eq!(literal_at(&rows, 0, 0), Literal::String("foo".to_owned()));
+        assert_eq!(literal_at(&rows, 0, 1), Literal::Number(NumberKind::U64(5)));
+        assert_eq!(literal_at(&rows, 0, 2), Literal::Bool(true));
+        assert_eq!(
+            literal_at(&rows, 1, 0),
+            Literal::String("bar, baz".to_owned())
+        );
+        assert_eq!(literal_at(&rows, 1, 1), Literal::String("5".to_owned()));
+        assert_eq!(literal_at(&rows, 1, 2), Literal::Null);
+
+        assert!(csv_rows("name, count\nfoo\n").is_err());
+        assert!(csv_rows("name\n\"foo\n").is_err());
+        assert!(csv_rows("").is_err());
+    }
+
+    #[test]
+    /// Test that JSON params must be a list of objects
+    fn json_params() {
+        let rows = json_rows(r#"[{"b": 1, "a": [1, 2]}, {"a": "x"}]"#)
+            .expect("Failed to parse valid JSON");
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0][0].0, "a");
+        assert_eq!(rows[0][1].0, "b");
+        assert!(matches!(
+            rows[0][0].1.borrow().unwrap().deref(),
+            DataKind::Collection(Collection::List(_))
+        ));
+        assert!(json_rows(r#"{"a": 1}"#).is_err());
+        assert!(json_rows("[1, 2]").is_err());
+    }
+}
@@ -0,0 +1,582 @@
+use crate::err_handle::ChimeraRuntimeFailure;
+use crate::literal::{match_candidates, unordered_matches, Collection, DataKind, Literal};
+use std::ops::Deref;
+
+/// Collections with more members than this only have their first members printed
+const MAX_MEMBERS: usize = 10;
+/// Collections nested deeper than this are collapsed to `{...}` or `[...]`
+const MAX_DEPTH: usize = 4;
+/// A pretty printed value longer than this many lines is cut off
+const MAX_LINES: usize = 30;
+/// A value printed on a single line longer than this many characters is cut off
+const MAX_INLINE_LEN: usize = 60;
+/// The most differences listed by a structural diff
+const MAX_DIFFERENCES: usize = 20;
+
+/// Print data as indented JSON, truncating large or deeply nested collections
+pub fn pretty_print(data: &DataKind) -> Result<String, ChimeraRuntimeFailure> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut first_line = String::new();
+    write_pretty(data, 0, &mut first_line, &mut lines)?;
+    lines.push(first_line);
+    if lines.len() > MAX_LINES {
+        let hidden = lines.len() - MAX_LINES;
+        lines.truncate(MAX_LINES);
+        lines.push(format!("... {} more lines", hidden));
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Print data on a single line, cutting it off if it is too long
+pub fn inline_print(data: &DataKind) -> Result<String, ChimeraRuntimeFailure> {
+    let printed = match data {
+        DataKind::Literal(literal) => literal_to_json(literal),
+        DataKind::Collection(_) => pretty_print(data)?
+            .lines()
+            .map(|line| line.trim())
+            .collect::<Vec<&str>>()
+            .join(" "),
+    };
+    if printed.chars().count() > MAX_INLINE_LEN {
+        let cut: String = printed.chars().take(MAX_INLINE_LEN).collect();
+        return Ok(format!("{}...", cut));
+    }
+    Ok(printed)
+}
+
+/// List how `actual` differs from `expected`, with each difference described by the path to it
+pub fn structural_diff(
+    actual: &DataKind,
+    expected: &DataKind,
+) -> Result<Vec<String>, ChimeraRuntimeFailure> {
+    let mut differences: Vec<String> = Vec::new();
+    diff_at_path(actual, expected, "", &mut differences)?;
+    if differences.len() > MAX_DIFFERENCES {
+        let hidden = differences.len() - MAX_DIFFERENCES;
+        differences.truncate(MAX_DIFFERENCES);
+        differences.push(format!("... {} more differences", hidden));
+    }
+    Ok(differences)
+}
+
+/// List how `actual` fails to contain `expected`. Objects in `actual` may have keys which are not
+/// in `expected`, and unordered lists match each expected item to a different actual item
+pub fn subset_diff(
+    actual: &DataKind,
+    expected: &DataKind,
+    unordered: bool,
+) -> Result<Vec<String>, ChimeraRuntimeFailure> {
+    let mut differences: Vec<String> = Vec::new();
+    subset_at_path(actual, expected, "", unordered, &mut differences)?;
+    if differences.len() > MAX_DIFFERENCES {
+        let hidden = differences.len() - MAX_DIFFERENCES;
+        differences.truncate(MAX_DIFFERENCES);
+        differences.push(format!("... {} more differences", hidden));
+    }
+    Ok(differences)
+}
+
+/// List the members which differ between `actual` and `expected` when they are compared as
+/// multisets. Anything besides two lists is compared as a whole
+pub fn unordered_diff(
+    actual: &DataKind,
+    expected: &DataKind,
+) -> Result<Vec<String>, ChimeraRuntimeFailure> {
+    let (
+        DataKind::Collection(Collection::List(actual_list)),
+        DataKind::Collection(Collection::List(expected_list)),
+    ) = (actual, expected)
+    else {
+        return Ok(vec![format!(
+            "value was {} but expected {}",
+            inline_print(actual)?,
+            inline_print(expected)?
+        )]);
+    };
+    let matched_to = unordered_matches(actual_list, expected_list)?;
+    let mut differences: Vec<String> = Vec::new();
+    for (member, matched) in actual_list.iter().zip(&matched_to) {
+        if matched.is_none() {
+            differences.push(format!("extra item {}", inline_print(&*member.borrow()?)?));
+        }
+    }
+    // Report missing members in the order they were expected
+    let mut was_matched = vec![false; expected_list.len()];
+    for expected_index in matched_to.into_iter().flatten() {
+        was_matched[expected_index] = true;
+    }
+    for (member, matched) in expected_list.iter().zip(was_matched) {
+        if !matched {
+            differences.push(format!(
+                "missing item {}",
+                inline_print(&*member.borrow()?)?
+            ));
+        }
+    }
+    if differences.len() > MAX_DIFFERENCES {
+        let hidden = differences.len() - MAX_DIFFERENCES;
+        differences.truncate(MAX_DIFFERENCES);
+        differences.push(format!("... {} more differences", hidden));
+    }
+    Ok(differences)
+}
+
+fn literal_to_json(literal: &Literal) -> String {
+    match literal {
+        Literal::String(s) => serde_json::to_string(s).unwrap_or_else(|_| format!("\"{}\"", s)),
+        other => other.to_string(),
+    }
+}
+
+// Writes `data` to the end of `current_line`, pushing finished lines into `lines`
+fn write_pretty(
+    data: &DataKind,
+    depth: usize,
+    current_line: &mut String,
+    lines: &mut Vec<String>,
+) -> Result<(), ChimeraRuntimeFailure> {
+    let indent = "  ".repeat(depth + 1);
+    let closing_indent = "  ".repeat(depth);
+    match data {
+        DataKind::Literal(literal) => current_line.push_str(&literal_to_json(literal)),
+        DataKind::Collection(Collection::Object(object)) => {
+            if object.is_empty() {
+                current_line.push_str("{}");
+            } else if depth >= MAX_DEPTH {
+                current_line.push_str("{...}");
+            } else {
+                current_line.push('{');
+                lines.push(std::mem::take(current_line));
+                // Sort keys so printed objects are stable between runs
+                let mut keys: Vec<&String> = object.keys().collect();
+                keys.sort();
+                for (index, key) in keys.iter().take(MAX_MEMBERS).enumerate() {
+                    current_line.push_str(&format!(
+                        "{}{}: ",
+                        indent,
+                        literal_to_json(&Literal::String((*key).clone()))
+                    ));
+                    write_pretty(
+                        object[*key].borrow()?.deref(),
+                        depth + 1,
+                        current_line,
+                        lines,
+                    )?;
+                    if index + 1 < keys.len() {
+                        current_line.push(',');
+                    }
+                    lines.push(std::mem::take(current_line));
+                }
+                if keys.len() > MAX_MEMBERS {
+                    lines.push(format!(
+                        "{}... {} more keys",
+                        indent,
+                        keys.len() - MAX_MEMBERS
+                    ));
+                }
+                current_line.push_str(&format!("{}}}", closing_indent));
+            }
+        }
+        DataKind::Collection(Collection::List(list)) => {
+            if list.is_empty() {
+                current_line.push_str("[]");
+            } else if depth >= MAX_DEPTH {
+                current_line.push_str("[...]");
+            } else {
+                current_line.push('[');
+                lines.push(std::mem::take(current_line));
+                for (index, member) in list.iter().take(MAX_MEMBERS).enumerate() {
+                    current_line.push_str(&indent);
+                    write_pretty(member.borrow()?.deref(), depth + 1, current_line, lines)?;
+                    if index + 1 < list.len() {
+                        current_line.push(',');
+                    }
+                    lines.push(std::mem::take(current_line));
+                }
+                if list.len() > MAX_MEMBERS {
+                    lines.push(format!(
+                        "{}... {} more items",
+                        indent,
+                        list.len() - MAX_MEMBERS
+                    ));
+                }
+                current_line.push_str(&format!("{}]", closing_indent));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn describe_location(path: &str) -> String {
+    match path.is_empty() {
+        true => "value".to_owned(),
+        false => format!("'{}'", path),
+    }
+}
+
+fn subset_at_path(
+    actual: &DataKind,
+    expected: &DataKind,
+    path: &str,
+    unordered: bool,
+    differences: &mut Vec<String>,
+) -> Result<(), ChimeraRuntimeFailure> {
+    match (actual, expected) {
+        (
+            DataKind::Collection(Collection::Object(actual_obj)),
+            DataKind::Collection(Collection::Object(expected_obj)),
+        ) => {
+            let mut keys: Vec<&String> = expected_obj.keys().collect();
+            keys.sort();
+            for key in keys {
+                let key_path = join_path(path, key);
+                match actual_obj.get(key) {
+                    Some(actual_val) => subset_at_path(
+                        actual_val.borrow()?.deref(),
+                        expected_obj[key].borrow()?.deref(),
+                        &key_path,
+                        unordered,
+                        differences,
+                    )?,
+                    None => differences.push(format!("missing key '{}'", key_path)),
+                }
+            }
+        }
+        (
+            DataKind::Collection(Collection::List(actual_list)),
+            DataKind::Collection(Collection::List(expected_list)),
+        ) => {
+            let actual_items = actual_list
+                .iter()
+                .map(|item| item.borrow())
+                .collect::<Result<Vec<_>, ChimeraRuntimeFailure>>()?;
+            let expected_items = expected_list
+                .iter()
+                .map(|item| item.borrow())
+                .collect::<Result<Vec<_>, ChimeraRuntimeFailure>>()?;
+            if unordered {
+                let actual_refs: Vec<&DataKind> = actual_items.iter().map(|i| i.deref()).collect();
+                let expected_refs: Vec<&DataKind> =
+                    expected_items.iter().map(|i| i.deref()).collect();
+                for index in unmatched_items(&actual_refs, &expected_refs)? {
+                    differences.push(format!(
+                        "no item in {} matches expected item {}",
+                        describe_location(path),
+                        inline_print(expected_refs[index])?
+                    ));
+                }
+            } else if actual_items.len() != expected_items.len() {
+                differences.push(format!(
+                    "{} has {} items but expected {}",
+                    describe_location(path),
+                    actual_items.len(),
+                    expected_items.len()
+                ));
+            } else {
+                for (index, (actual_item, expected_item)) in
+                    actual_items.iter().zip(expected_items.iter()).enumerate()
+                {
+                    subset_at_path(
+                        actual_item,
+                        expected_item,
+                        &join_path(path, &index.to_string()),
+                        unordered,
+                        differences,
+                    )?;
+                }
+            }
+        }
+        _ => {
+            if actual != expected {
+                differences.push(format!(
+                    "{} was {} but expected {}",
+                    describe_location(path),
+                    inline_print(actual)?,
+                    inline_print(expected)?
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Pairs each expected item with a different actual item which contains it, returning the indexes
+// of the expected items left without a match
+fn unmatched_items(
+    actual: &[&DataKind],
+    expected: &[&DataKind],
+) -> Result<Vec<usize>, ChimeraRuntimeFailure> {
+    let mut candidates: Vec<Vec<usize>> = Vec::with_capacity(expected.len());
+    for expected_item in expected {
+        let mut matching: Vec<usize> = Vec::new();
+        for (index, actual_item) in actual.iter().enumerate() {
+            let mut differences: Vec<String> = Vec::new();
+            subset_at_path(actual_item, expected_item, "", true, &mut differences)?;
+            if differences.is_empty() {
+                matching.push(index);
+            }
+        }
+        candidates.push(matching);
+    }
+    let mut was_matched = vec![false; expected.len()];
+    for expected_index in match_candidates(actual.len(), &candidates)
+        .into_iter()
+        .flatten()
+    {
+        was_matched[expected_index] = true;
+    }
+    Ok((0..expected.len()).filter(|&i| !was_matched[i]).collect())
+}
+
+fn diff_at_path(
+    actual: &DataKind,
+    expected: &DataKind,
+    path: &str,
+    differences: &mut Vec<String>,
+) -> Result<(), ChimeraRuntimeFailure> {
+    match (actual, expected) {
+        (
+            DataKind::Collection(Collection::Object(actual_obj)),
+            DataKind::Collection(Collection::Object(expected_obj)),
+        ) => {
+            let mut keys: Vec<&String> = expected_obj.keys().chain(actual_obj.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let key_path = join_path(path, key);
+                match (actual_obj.get(key), expected_obj.get(key)) {
+                    (Some(actual_val), Some(expected_val)) => diff_at_path(
+                        actual_val.borrow()?.deref(),
+                        expected_val.borrow()?.deref(),
+                        &key_path,
+                        differences,
+                    )?,
+                    (None, Some(_)) => differences.push(format!("missing key '{}'", key_path)),
+                    (Some(_), None) => differences.push(format!("extra key '{}'", key_path)),
+                    (None, None) => (),
+                }
+            }
+        }
+        (
+            DataKind::Collection(Collection::List(actual_list)),
+            DataKind::Collection(Collection::List(expected_list)),
+        ) => {
+            let longest = actual_list.len().max(expected_list.len());
+            for index in 0..longest {
+                let index_path = join_path(path, &index.to_string());
+                match (actual_list.get(index), expected_list.get(index)) {
+                    (Some(actual_val), Some(expected_val)) => diff_at_path(
+                        actual_val.borrow()?.deref(),
+                        expected_val.borrow()?.deref(),
+                        &index_path,
+                        differences,
+                    )?,
+                    (None, Some(_)) => differences.push(format!("missing item '{}'", index_path)),
+                    (Some(_), None) => differences.push(format!("extra item '{}'", index_path)),
+                    (None, None) => (),
+                }
+            }
+        }
+        _ => {
+            if actual != expected {
+                differences.push(format!(
+                    "{} was {} but expected {}",
+                    describe_location(path),
+                    inline_print(actual)?,
+                    inline_print(expected)?
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod pretty_tests {
+    use super::*;
+    use crate::literal::{Data, NumberKind};
+    use std::collections::HashMap;
+
+    fn object(members: Vec<(&str, DataKind)>) -> DataKind {
+        let mut map: HashMap<String, Data> = HashMap::new();
+        for (key, value) in members {
+            map.insert(key.to_owned(), Data::new(value));
+        }
+        DataKind::Collection(Collection::Object(map))
+    }
+
+    fn number(n: u64) -> DataKind {
+        DataKind::Literal(Literal::Number(NumberKind::U64(n)))
+    }
+
+    fn string(s: &str) -> DataKind {
+        DataKind::Literal(Literal::String(s.to_owned()))
+    }
+
+    #[test]
+    /// Test that collections are printed as indented JSON with sorted keys
+    fn pretty_print_collections() {
+        let data = object(vec![
+            ("name", string("foo")),
+            (
+                "ids",
+                DataKind::Collection(Collection::List(vec![Data::new(number(1))])),
+            ),
+            ("empty", object(vec![])),
+        ]);
+        assert_eq!(
+            pretty_print(&data).unwrap(),
+            "{\n  \"empty\": {},\n  \"ids\": [\n    1\n  ],\n  \"name\": \"foo\"\n}"
+        );
+        assert_eq!(pretty_print(&string("foo")).unwrap(), "\"foo\"");
+    }
+
+    #[test]
+    /// Test that large collections are truncated
+    fn truncation() {
+        let list: Vec<Data> = (0..25).map(|i| Data::new(number(i))).collect();
+        let printed = pretty_print(&DataKind::Collection(Collection::List(list))).unwrap();
+        assert!(printed.contains("... 15 more items"));
+
+        let mut nested = number(1);
+        for _ in 0..6 {
+            nested = object(vec![("a", nested)]);
+        }
+        assert!(pretty_print(&nested).unwrap().contains("{...}"));
+
+        let long_string = string(&"a".repeat(100));
+        assert!(inline_print(&long_string).unwrap().ends_with("..."));
+    }
+
+    #[test]
+    /// Test that a structural diff lists missing keys, extra keys, and differing values by path
+    fn diff_collections() {
+        let actual = object(vec![
+            ("name", string("bar")),
+            ("extra", number(1)),
+            ("nested", object(vec![("count", number(2))])),
+        ]);
+        let expected = object(vec![
+            ("name", string("foo")),
+            ("missing", number(1)),
+            ("nested", object(vec![("count", number(3))])),
+        ]);
+        assert_eq!(
+            structural_diff(&actual, &expected).unwrap(),
+            vec![
+                "extra key 'extra'".to_owned(),
+                "missing key 'missing'".to_owned(),
+                "'name' was \"bar\" but expected \"foo\"".to_owned(),
+                "'nested.count' was 2 but expected 3".to_owned(),
+            ]
+        );
+    }
+
+    #[test]
+    /// Test that a subset diff ignores extra keys and can match list members in any order
+    fn subset_collections() {
+        let actual = object(vec![
+            ("name", string("foo")),
+            ("extra", number(1)),
+            (
+                "ids",
+                DataKind::Collection(Collection::List(vec![
+                    Data::new(number(1)),
+                    Data::new(number(2)),
+                ])),
+            ),
+        ]);
+        let expected = object(vec![
+            ("name", string("foo")),
+            (
+                "ids",
+                DataKind::Collection(Collection::List(vec![
+                    Data::new(number(2)),
+                    Data::new(number(1)),
+                ])),
+            ),
+        ]);
+        assert!(subset_diff(&actual, &expected, true).unwrap().is_empty());
+        assert_eq!(
+            subset_diff(&actual, &expected, false).unwrap(),
+            vec![
+                "'ids.0' was 1 but expected 2".to_owned(),
+                "'ids.1' was 2 but expected 1".to_owned(),
+            ]
+        );
+        assert_eq!(
+            subset_diff(&expected, &actual, true).unwrap(),
+            vec!["missing key 'extra'".to_owned()]
+        );
+
+        // Each expected item needs its own match, even when an earlier item took the only
+        // match for a later one
+        let loose = || object(vec![("a", number(1))]);
+        let strict = || object(vec![("a", number(1)), ("b", number(2))]);
+        let actual_list = DataKind::Collection(Collection::List(vec![
+            Data::new(strict()),
+            Data::new(object(vec![("a", number(1)), ("c", number(3))])),
+        ]));
+        let expected_list = DataKind::Collection(Collection::List(vec![
+            Data::new(loose()),
+            Data::new(strict()),
+        ]));
+        assert!(subset_diff(&actual_list, &expected_list, true)
+            .unwrap()
+            .is_empty());
+        let duplicates = DataKind::Collection(Collection::List(vec![
+            Data::new(loose()),
+            Data::new(loose()),
+            Data::new(loose()),
+        ]));
+        assert_eq!(
+            subset_diff(&actual_list, &duplicates, true).unwrap(),
+            vec!["no item in value matches expected item { \"a\": 1 }".to_owned()]
+        );
+    }
+
+    #[test]
+    /// Test that an unordered diff lists the members which are extra or missing
+    fn unordered_list_diff() {
+        let list = |numbers: Vec<u64>| {
+            DataKind::Collection(Collection::List(
+                numbers.into_iter().map(|n| Data::new(number(n))).collect(),
+            ))
+        };
+        assert!(unordered_diff(&list(vec![2, 1, 2]), &list(vec![2, 2, 1]))
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            unordered_diff(&list(vec![1, 3, 3]), &list(vec![3, 2, 1])).unwrap(),
+            vec!["extra item 3".to_owned(), "missing item 2".to_owned()]
+        );
+        // NaN does not equal itself, so it is both extra and missing
+        let floats = |numbers: Vec<f64>| {
+            DataKind::Collection(Collection::List(
+                numbers
+                    .into_iter()
+                    .map(|n| Data::new(DataKind::Literal(Literal::Number(NumberKind::F64(n)))))
+                    .collect(),
+            ))
+        };
+        assert_eq!(
+            unordered_diff(&floats(vec![f64::NAN, 1e40]), &floats(vec![1e40, f64::NAN])).unwrap(),
+            vec!["extra item NaN".to_owned(), "missing item NaN".to_owned()]
+        );
+        assert_eq!(
+            unordered_diff(&floats(vec![1e40]), &floats(vec![1e41])).unwrap(),
+            vec![
+                "extra item 10000000000000000000000000000000000000000".to_owned(),
+                "missing item 100000000000000000000000000000000000000000".to_owned()
+            ]
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use crate::err_handle::ChimeraRuntimeFailure;
+use crate::frontend::Context;
+use crate::literal::DataKind;
+use crate::util::pretty::structural_diff;
+use serde_json::Value as JsonValue;
+use std::fs;
+use std::path::Path;
+
+/// Snapshots are stored in this directory, next to the test file which uses them
+const SNAPSHOT_DIRECTORY: &str = "__snapshots__";
+
+/// Compare data against the snapshot called `name` and list how it differs, leaving out the fields
+/// at each of the `ignore` paths. When `update` is set the snapshot is written instead
+pub fn snapshot_differences(
+    context: &Context,
+    name: &str,
+    data: &DataKind,
+    ignore: &[String],
+    update: bool,
+) -> Result<Vec<String>, ChimeraRuntimeFailure> {
+    let snapshot_error =
+        |reason: String| ChimeraRuntimeFailure::SnapshotError(reason, context.current_line);
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(snapshot_error(format!(
+            "'{}' is not a valid snapshot name, it must not be empty, start with '.', or contain a slash",
+            name
+        )));
+    }
+    let path = context
+        .get_test_directory()
+        .join(SNAPSHOT_DIRECTORY)
+        .join(format!("{}.json", name));
+    let mut actual = serde_json::to_value(data).map_err(|_| {
+        ChimeraRuntimeFailure::InternalError("converting data into JSON".to_owned())
+    })?;
+    if update {
+        if write_snapshot(&path, &actual).map_err(snapshot_error)? {
+            context.log_verbose(format!("SNAPSHOT - wrote '{}'", path.display()));
+        }
+        return Ok(Vec::new());
+    }
+
+    let contents = fs::read_to_string(&path).map_err(|_| {
+        snapshot_error(format!(
+            "Snapshot '{}' does not exist at '{}', run with --update-snapshots to create it",
+            name,
+            path.display()
+        ))
+    })?;
+    let mut expected: JsonValue = serde_json::from_str(&contents).map_err(|e| {
+        snapshot_error(format!(
+            "Snapshot file '{}' is not valid JSON, {}",
+            path.display(),
+            e
+        ))
+    })?;
+    for ignore_path in ignore {
+        let segments: Vec<&str> = ignore_path.split('.').collect();
+        remove_path(&mut actual, &segments);
+        remove_path(&mut expected, &segments);
+    }
+    // Compare as data so numbers are equal the same way they are for an EQUALS
+    let to_data = |json: JsonValue| {
+        serde_json::from_value::<DataKind>(json).map_err(|_| {
+            ChimeraRuntimeFailure::InternalError("converting JSON into data".to_owned())
+        })
+    };
+    structural_diff(&to_data(actual)?, &to_data(expected)?)
+}
+
+// Writes the snapshot as indented JSON, returning false if it already held the same contents
+fn write_snapshot(path: &Path, json: &JsonValue) -> Result<bool, String> {
+    let mut contents = serde_json::to_string_pretty(json)
+        .map_err(|e| format!("Failed to convert a snapshot into JSON, {}", e))?;
+    contents.push('\n');
+    if fs::read_to_string(path).is_ok_and(|existing| existing == contents) {
+        return Ok(false);
+    }
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory).map_err(|e| {
+            format!(
+                "Failed to create the snapshot directory '{}', {}",
+                directory.display(),
+                e
+            )
+        })?;
+    }
+    fs::write(path, contents)
+        .map_err(|e| format!("Failed to write snapshot '{}', {}", path.display(), e))?;
+    Ok(true)
+}
+
+// Removes the field at a dot separated path, where a `*` segment matches every key or index.
+// List items are replaced with null rather than removed so the indexes of later items are kept
+fn remove_path(json: &mut JsonValue, segments: &[&str]) {
+    let Some((first, rest)) = segments.split_first() else {
+        return;
+    };
+    match json {
+        JsonValue::Object(map) => {
+            if rest.is_empty() {
+                match *first {
+                    "*" => map.clear(),
+                    key => {
+                        map.remove(key);
+                    }
+                }
+            } else if *first == "*" {
+                map.values_mut().for_each(|child| remove_path(child, rest));
+            } else if let Some(child) = map.get_mut(*first) {
+                remove_path(child, rest);
+            }
+        }
+        JsonValue::Array(items) => {
+            let children: Vec<&mut JsonValue> = match *first {
+                "*" => items.iter_mut().collect(),
+                index => match index.parse::<usize>().ok().and_then(|i| items.get_mut(i)) {
+                    Some(child) => vec![child],
+                    None => Vec::new(),
+                },
+            };
+            for child in children {
+                match rest.is_empty() {
+                    true => *child = JsonValue::Null,
+                    false => remove_path(child, rest),
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod snapshot_tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    /// Test that ignored paths are removed from objects and nulled out in lists
+    fn remove_ignored_paths() {
+        let mut json = json!({
+            "id": 5,
+            "name": "foo",
+            "items": [
+                {"id": 1, "created_at": "today"},
+                {"id": 2, "created_at": "yesterday"}
+            ]
+        });
+        remove_path(&mut json, &["id"]);
+        remove_path(&mut json, &["items", "*", "created_at"]);
+        remove_path(&mut json, &["items", "1", "id"]);
+        remove_path(&mut json, &["missing", "path"]);
+        assert_eq!(json, json!({"name": "foo", "items": [{"id": 1}, {}]}));
+        remove_path(&mut json, &["items", "0"]);
+        assert_eq!(json, json!({"name": "foo", "items": [null, {}]}));
+    }
+
+    #[test]
+    /// Test that a snapshot is only rewritten when its contents change
+    fn write_snapshot_file() {
+        let directory = std::env::temp_dir().join(format!("chs_snapshots_{}", std::process::id()));
+        let path = directory.join("nested").join("snapshot.json");
+        let json = json!({"b": [1, 2], "a": "foo"});
+        assert_eq!(write_snapshot(&path, &json), Ok(true));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "{\n  \"a\": \"foo\",\n  \"b\": [\n    1,\n    2\n  ]\n}\n"
+        );
+        assert_eq!(write_snapshot(&path, &json), Ok(false));
+        assert_eq!(write_snapshot(&path, &json!({"a": "bar"})), Ok(true));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
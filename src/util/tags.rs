@@ -0,0 +1,189 @@
+use crate::abstract_syntax_tree::{BlockContents, Function};
+
+/// Which tests to run based on their tags, from the `--tag` and `--exclude-tag` arguments.
+/// A nested case has its own tags along with every tag of the cases it is nested in
+#[derive(Debug)]
+pub struct TagFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl TagFilter {
+    pub fn new(include: Vec<String>, exclude: Vec<String>) -> Self {
+        Self { include, exclude }
+    }
+
+    /// Whether a function which is not nested should run, either itself or for one of its nested cases
+    pub fn selects(&self, function: &Function) -> bool {
+        self.selects_nested(function, &[])
+    }
+
+    /// Whether a function which is not nested has an excluded tag
+    pub fn excludes(&self, function: &Function) -> bool {
+        function
+            .tags()
+            .iter()
+            .any(|tag| self.exclude.iter().any(|excluded| excluded == tag))
+    }
+
+    /// Remove every case nested in a function which should not run. A case without an included tag
+    /// still runs when one of its nested cases has one, as the nested case may rely on it
+    pub fn prune(&self, function: Function) -> Function {
+        let tags = Self::tags_with(&function, &[]);
+        self.prune_nested(function, &tags)
+    }
+
+    // A function's own tags along with every tag it inherits from the cases it is nested in
+    fn tags_with(function: &Function, inherited: &[String]) -> Vec<String> {
+        let mut tags = inherited.to_vec();
+        tags.extend(function.tags().into_iter().map(|tag| tag.to_owned()));
+        tags
+    }
+
+    fn selects_nested(&self, function: &Function, inherited: &[String]) -> bool {
+        let tags = Self::tags_with(function, inherited);
+        if tags.iter().any(|tag| self.exclude.contains(tag)) {
+            return false;
+        }
+        let included = self.include.is_empty() || tags.iter().any(|tag| self.include.contains(tag));
+        included
+            || function
+                .block
+                .iter()
+                .any(|block_contents| match block_contents {
+                    BlockContents::Function(nested_function) => {
+                        self.selects_nested(nested_function, &tags)
+                    }
+                    _ => false,
+                })
+    }
+
+    fn prune_nested(&self, mut function: Function, tags: &[String]) -> Function {
+        function.block = function
+            .block
+            .into_iter()
+            .filter_map(|block_contents| match block_contents {
+                BlockContents::Function(nested_function) => {
+                    if !self.selects_nested(&nested_function, tags) {
+                        return None;
+                    }
+                    let nested_tags = Self::tags_with(&nested_function, tags);
+                    Some(BlockContents::Function(
+                        self.prune_nested(nested_function, &nested_tags),
+                    ))
+                }
+                other => Some(other),
+            })
+            .collect();
+        function
+    }
+}
+
+#[cfg(test)]
+mod tag_tests {
+    use super::*;
+    use crate::abstract_syntax_tree::ChimeraScriptAST;
+
+    const TAGGED_FILE: &str = r#"[test, tags=smoke|billing]
+case smoke-billing() {
+  PRINT 1;
+}
+
+[test, tags=slow]
+case slow() {
+  PRINT 1;
+}
+
+[test]
+case untagged-parent() {
+  PRINT 1;
+  [tags=smoke]
+  case smoke-child() {
+    PRINT 1;
+  }
+  [tags=slow]
+  case slow-child() {
+    PRINT 1;
+  }
+}
+"#;
+
+    // The names of each function and the cases nested in them, in the order they would run
+    fn names(functions: &[Function]) -> Vec<String> {
+        let mut all_names = Vec::new();
+        for function in functions {
+            all_names.push(function.name.clone());
+            for block_contents in &function.block {
+                if let BlockContents::Function(nested_function) = block_contents {
+                    all_names.extend(names(std::slice::from_ref(nested_function)));
+                }
+            }
+        }
+        all_names
+    }
+
+    fn filtered_names(include: &[&str], exclude: &[&str]) -> Vec<String> {
+        let ast = ChimeraScriptAST::new(TAGGED_FILE).expect("Failed to parse a tagged file");
+        let tag_filter = TagFilter::new(
+            include.iter().map(|tag| tag.to_string()).collect(),
+            exclude.iter().map(|tag| tag.to_string()).collect(),
+        );
+        let selected: Vec<Function> = ast
+            .functions
+            .into_iter()
+            .filter(|function| tag_filter.selects(function))
+            .map(|function| tag_filter.prune(function))
+            .collect();
+        names(&selected)
+    }
+
+    #[test]
+    /// Test that tags select tests, including a parent which is only needed for a nested case
+    fn include_tags() {
+        assert_eq!(
+            filtered_names(&[], &[]),
+            vec![
+                "smoke-billing",
+                "slow",
+                "untagged-parent",
+                "smoke-child",
+                "slow-child"
+            ]
+        );
+        assert_eq!(
+            filtered_names(&["smoke"], &[]),
+            vec!["smoke-billing", "untagged-parent", "smoke-child"]
+        );
+        assert_eq!(
+            filtered_names(&["billing", "slow"], &[]),
+            vec!["smoke-billing", "slow", "untagged-parent", "slow-child"]
+        );
+        assert!(filtered_names(&["nightly"], &[]).is_empty());
+    }
+
+    #[test]
+    /// Test that excluded tags remove tests, and are inherited by nested cases
+    fn exclude_tags() {
+        assert_eq!(
+            filtered_names(&[], &["slow"]),
+            vec!["smoke-billing", "untagged-parent", "smoke-child"]
+        );
+        assert_eq!(
+            filtered_names(&["smoke"], &["billing"]),
+            vec!["untagged-parent", "smoke-child"]
+        );
+    }
+
+    #[test]
+    /// Test that only a function's own excluded tags exclude it, not the tags of its nested cases
+    fn excludes() {
+        let ast = ChimeraScriptAST::new(TAGGED_FILE).expect("Failed to parse a tagged file");
+        let tag_filter = TagFilter::new(Vec::new(), vec!["slow".to_owned()]);
+        let excluded: Vec<bool> = ast
+            .functions
+            .iter()
+            .map(|function| tag_filter.excludes(function))
+            .collect();
+        assert_eq!(excluded, vec![false, true, false]);
+    }
+}
@@ -0,0 +1,528 @@
+use crate::abstract_syntax_tree::HTTPVerb;
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+#[cfg(unix)]
+use reqwest::Url;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::io::ErrorKind;
+#[cfg(unix)]
+use std::io::{Read, Write};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// The status code, headers, and raw body bytes of an HTTP response, before the body is interpreted
+pub struct RawResponse {
+    pub status_code: u16,
+    /// Header names and values in the order they were received, names are lowercase
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// The class of failure that stopped a request from getting a response. These are also the names
+/// used to pick which failures are retried, e.g. `retry_on = ["connect", "timeout"]`
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportError {
+    /// The connection to the target could not be opened, e.g. it was refused
+    Connect,
+    /// The target did not respond in time
+    Timeout,
+    Other,
+}
+
+impl Display for TransportError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransportError::Connect => write!(f, "connect"),
+            TransportError::Timeout => write!(f, "timeout"),
+            TransportError::Other => write!(f, "other"),
+        }
+    }
+}
+
+impl FromStr for TransportError {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "connect" => Ok(TransportError::Connect),
+            "timeout" => Ok(TransportError::Timeout),
+            "other" => Ok(TransportError::Other),
+            _ => Err(()),
+        }
+    }
+}
+
+impl From<reqwest::Error> for TransportError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_connect() {
+            TransportError::Connect
+        } else if e.is_timeout() {
+            TransportError::Timeout
+        } else {
+            TransportError::Other
+        }
+    }
+}
+
+impl From<std::io::Error> for TransportError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            ErrorKind::TimedOut | ErrorKind::WouldBlock => TransportError::Timeout,
+            _ => TransportError::Other,
+        }
+    }
+}
+
+/// A Transport is the layer that actually moves an HTTP request to the target and reads back
+/// the response. A `RealClient` resolves an `HttpCommand` into a url, headers, and a body and then
+/// hands them off to its Transport. A `timeout` is the time left before the test's deadline
+pub trait Transport: Debug {
+    fn send(
+        &self,
+        verb: &HTTPVerb,
+        url: &str,
+        headers: HeaderMap,
+        body: &HashMap<String, String>,
+        timeout: Option<Duration>,
+    ) -> Result<RawResponse, TransportError>;
+}
+
+#[derive(Debug)]
+pub struct ReqwestTransport {
+    client: reqwest::blocking::Client,
+}
+
+impl ReqwestTransport {
+    pub fn new(client: reqwest::blocking::Client) -> Self {
+        Self { client }
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn send(
+        &self,
+        verb: &HTTPVerb,
+        url: &str,
+        headers: HeaderMap,
+        body: &HashMap<String, String>,
+        timeout: Option<Duration>,
+    ) -> Result<RawResponse, TransportError> {
+        let request = match verb {
+            HTTPVerb::Get => self.client.get(url),
+            HTTPVerb::Delete => self.client.delete(url),
+            HTTPVerb::Post => self.client.post(url).json(body),
+            HTTPVerb::Put => self.client.put(url).json(body),
+        };
+        let request = match timeout {
+            Some(timeout) => request.timeout(timeout),
+            None => request,
+        };
+        let res = request.headers(headers).send();
+        match res {
+            Ok(response) => {
+                // Have to store the status and headers here as reading the body consumes the response
+                let status_code = response.status().as_u16();
+                let headers = response
+                    .headers()
+                    .iter()
+                    .map(|(name, value)| {
+                        (
+                            name.as_str().to_owned(),
+                            String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                        )
+                    })
+                    .collect();
+                let body = response.bytes()?.to_vec();
+                Ok(RawResponse {
+                    status_code,
+                    headers,
+                    body,
+                })
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Sends requests as plain HTTP/1.1 over a Unix domain socket. Each request opens a new connection
+/// and asks the server to close it once the response is written, so the end of the response is
+/// the end of the stream
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct UnixSocketTransport {
+    socket_path: PathBuf,
+}
+
+#[cfg(unix)]
+impl UnixSocketTransport {
+    pub fn new(socket_path: PathBuf) -> Self {
+        Self { socket_path }
+    }
+}
+
+#[cfg(unix)]
+impl Transport for UnixSocketTransport {
+    fn send(
+        &self,
+        verb: &HTTPVerb,
+        url: &str,
+        headers: HeaderMap,
+        body: &HashMap<String, String>,
+        timeout: Option<Duration>,
+    ) -> Result<RawResponse, TransportError> {
+        // Parsing the url percent-encodes the path and query, the same as reqwest would
+        let parsed_url = Url::parse(url).map_err(|_| TransportError::Other)?;
+        let mut request_target = parsed_url.path().to_owned();
+        if let Some(query) = parsed_url.query() {
+            request_target.push('?');
+            request_target.push_str(query);
+        }
+        let method = match verb {
+            HTTPVerb::Get => "GET",
+            HTTPVerb::Put => "PUT",
+            HTTPVerb::Post => "POST",
+            HTTPVerb::Delete => "DELETE",
+        };
+        let host = parsed_url.host_str().unwrap_or("localhost");
+
+        let mut request: Vec<u8> = Vec::new();
+        write!(
+            request,
+            "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n",
+            method, request_target, host
+        )
+        .map_err(|_| TransportError::Other)?;
+        for (name, value) in headers.iter() {
+            let value = value.to_str().map_err(|_| TransportError::Other)?;
+            write!(request, "{}: {}\r\n", name, value).map_err(|_| TransportError::Other)?;
+        }
+        // Match reqwest, which always sends a JSON body for PUT and POST, and only sets its
+        // Content-Type when the request did not set its own
+        let request_body = match verb {
+            HTTPVerb::Put | HTTPVerb::Post => {
+                let json = serde_json::to_vec(body).map_err(|_| TransportError::Other)?;
+                if !headers.contains_key(CONTENT_TYPE) {
+                    write!(request, "Content-Type: application/json\r\n")
+                        .map_err(|_| TransportError::Other)?;
+                }
+                write!(request, "Content-Length: {}\r\n", json.len())
+                    .map_err(|_| TransportError::Other)?;
+                json
+            }
+            HTTPVerb::Get | HTTPVerb::Delete => Vec::new(),
+        };
+        request.extend_from_slice(b"\r\n");
+        request.extend_from_slice(&request_body);
+
+        // Any failure to open the socket, such as it not existing yet, is a connection failure
+        let mut stream =
+            UnixStream::connect(&self.socket_path).map_err(|_| TransportError::Connect)?;
+        // A zero timeout is rejected by the socket, and the deadline check catches it right after
+        let timeout = timeout.map(|timeout| timeout.max(Duration::from_millis(1)));
+        stream.set_read_timeout(timeout)?;
+        stream.set_write_timeout(timeout)?;
+        stream.write_all(&request)?;
+        let mut response: Vec<u8> = Vec::new();
+        stream.read_to_end(&mut response)?;
+        parse_http_response(&response)
+    }
+}
+
+/// Parse a raw HTTP/1.1 response into its status code, headers, and body
+#[cfg(unix)]
+fn parse_http_response(mut response: &[u8]) -> Result<RawResponse, TransportError> {
+    // Interim responses like `100 Continue` or `103 Early Hints` come before the final response,
+    // and have a head but no body
+    let (status_code, head, raw_body) = loop {
+        let header_end = response
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .ok_or(TransportError::Other)?;
+        let head =
+            std::str::from_utf8(&response[..header_end]).map_err(|_| TransportError::Other)?;
+        let raw_body = &response[header_end + 4..];
+        // A status line looks like `HTTP/1.1 200 OK`
+        let status_code: u16 = head
+            .split("\r\n")
+            .next()
+            .and_then(|status_line| status_line.split(' ').nth(1))
+            .and_then(|code| code.parse().ok())
+            .ok_or(TransportError::Other)?;
+        // 101 Switching Protocols is the final response to an upgrade, not an interim one
+        if (100..200).contains(&status_code) && status_code != 101 {
+            response = raw_body;
+            continue;
+        }
+        break (status_code, head, raw_body);
+    };
+    // The status line was already read
+    let lines = head.split("\r\n").skip(1);
+
+    let mut content_length: Option<usize> = None;
+    let mut is_chunked = false;
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in lines {
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = Some(value.parse().map_err(|_| TransportError::Other)?);
+            } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                is_chunked = value.eq_ignore_ascii_case("chunked");
+            }
+            headers.push((name.trim().to_ascii_lowercase(), value.to_owned()));
+        }
+    }
+
+    let body = if is_chunked {
+        decode_chunked_body(raw_body)?
+    } else {
+        match content_length {
+            Some(len) => raw_body.get(..len).ok_or(TransportError::Other)?.to_vec(),
+            None => raw_body.to_vec(),
+        }
+    };
+    Ok(RawResponse {
+        status_code,
+        headers,
+        body,
+    })
+}
+
+#[cfg(unix)]
+fn decode_chunked_body(mut raw_body: &[u8]) -> Result<Vec<u8>, TransportError> {
+    let mut body: Vec<u8> = Vec::new();
+    loop {
+        let size_end = raw_body
+            .windows(2)
+            .position(|window| window == b"\r\n")
+            .ok_or(TransportError::Other)?;
+        let size_line =
+            std::str::from_utf8(&raw_body[..size_end]).map_err(|_| TransportError::Other)?;
+        // Chunk sizes are hex and can be followed by `;extension` values which are ignored
+        let size_str = size_line.split(';').next().unwrap_or("").trim();
+        let size = usize::from_str_radix(size_str, 16).map_err(|_| TransportError::Other)?;
+        if size == 0 {
+            return Ok(body);
+        }
+        let chunk_start = size_end + 2;
+        let chunk = raw_body
+            .get(chunk_start..chunk_start + size)
+            .ok_or(TransportError::Other)?;
+        body.extend_from_slice(chunk);
+        // Skip the chunk and the CRLF which follows it
+        raw_body = raw_body
+            .get(chunk_start + size + 2..)
+            .ok_or(TransportError::Other)?;
+    }
+}
+
+#[cfg(all(test, unix))]
+mod transport_tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+
+    /// Start a server on a Unix socket that answers a single request with `response` and
+    /// returns the raw request it received
+    fn serve_once(name: &str, response: &'static str) -> (PathBuf, thread::JoinHandle<String>) {
+        let socket_path = std::env::temp_dir().join(format!(
+            "chimerascript-{}-{}.sock",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&socket_path);
+        let listener = UnixListener::bind(&socket_path).expect("Failed to bind a test socket");
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("Failed to accept a connection");
+            let mut request: Vec<u8> = Vec::new();
+            let mut buffer = [0u8; 1024];
+            // Read until the end of the request head and any body it declared
+            loop {
+                let read = stream.read(&mut buffer).expect("Failed to read a request");
+                request.extend_from_slice(&buffer[..read]);
+                let as_str = String::from_utf8_lossy(&request).to_string();
+                if let Some(head_end) = as_str.find("\r\n\r\n") {
+                    let content_length = as_str
+                        .lines()
+                        .find_map(|line| line.strip_prefix("Content-Length: "))
+                        .map(|len| len.trim().parse::<usize>().unwrap())
+                        .unwrap_or(0);
+                    if request.len() >= head_end + 4 + content_length {
+                        break;
+                    }
+                }
+            }
+            stream
+                .write_all(response.as_bytes())
+                .expect("Failed to write a response");
+            String::from_utf8(request).unwrap()
+        });
+        (socket_path, handle)
+    }
+
+    #[test]
+    /// Test a request with a query param and a content-length response body
+    fn unix_socket_get() {
+        let (socket_path, handle) = serve_once(
+            "get",
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 11\r\n\r\n{\"id\": 42}\n",
+        );
+        let transport = UnixSocketTransport::new(socket_path.clone());
+        let res = transport
+            .send(
+                &HTTPVerb::Get,
+                "http://localhost/users?name=some user",
+                HeaderMap::new(),
+                &HashMap::new(),
+                None,
+            )
+            .expect("Request over a unix socket should succeed");
+        let request = handle.join().unwrap();
+        let _ = std::fs::remove_file(socket_path);
+        assert!(request.starts_with("GET /users?name=some%20user HTTP/1.1\r\n"));
+        assert!(request.contains("Host: localhost\r\n"));
+        assert_eq!(res.status_code, 200);
+        assert_eq!(
+            res.headers,
+            vec![
+                ("content-type".to_owned(), "application/json".to_owned()),
+                ("content-length".to_owned(), "11".to_owned())
+            ]
+        );
+        assert_eq!(res.body, b"{\"id\": 42}\n");
+    }
+
+    #[test]
+    /// Test that interim responses before the final response are skipped
+    fn unix_socket_interim_responses() {
+        let (socket_path, handle) = serve_once(
+            "interim",
+            "HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 103 Early Hints\r\nLink: </style.css>; rel=preload\r\n\r\nHTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}",
+        );
+        let transport = UnixSocketTransport::new(socket_path.clone());
+        let res = transport
+            .send(
+                &HTTPVerb::Get,
+                "http://localhost/users",
+                HeaderMap::new(),
+                &HashMap::new(),
+                None,
+            )
+            .expect("Request over a unix socket should succeed");
+        handle.join().unwrap();
+        let _ = std::fs::remove_file(socket_path);
+        assert_eq!(res.status_code, 200);
+        assert_eq!(
+            res.headers,
+            vec![("content-length".to_owned(), "2".to_owned())]
+        );
+        assert_eq!(res.body, b"{}");
+    }
+
+    #[test]
+    /// Test a request with a JSON body and a chunked response body
+    fn unix_socket_post_chunked() {
+        let (socket_path, handle) = serve_once(
+            "post",
+            "HTTP/1.1 201 Created\r\nTransfer-Encoding: chunked\r\n\r\n5\r\n{\"ok\"\r\n6\r\n: true\r\n1\r\n}\r\n0\r\n\r\n",
+        );
+        let transport = UnixSocketTransport::new(socket_path.clone());
+        let mut body: HashMap<String, String> = HashMap::new();
+        body.insert("name".to_owned(), "dog".to_owned());
+        let res = transport
+            .send(
+                &HTTPVerb::Post,
+                "http://localhost/pets",
+                HeaderMap::new(),
+                &body,
+                None,
+            )
+            .expect("Request over a unix socket should succeed");
+        let request = handle.join().unwrap();
+        let _ = std::fs::remove_file(socket_path);
+        assert!(request.starts_with("POST /pets HTTP/1.1\r\n"));
+        assert!(request.contains("Content-Type: application/json\r\n"));
+        assert!(request.ends_with("\r\n\r\n{\"name\":\"dog\"}"));
+        assert_eq!(res.status_code, 201);
+        assert_eq!(res.body, b"{\"ok\": true}");
+    }
+
+    #[test]
+    /// Test that a request which sets its own Content-Type sends only that one
+    fn unix_socket_own_content_type() {
+        let (socket_path, handle) = serve_once(
+            "content-type",
+            "HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n",
+        );
+        let transport = UnixSocketTransport::new(socket_path.clone());
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            "application/merge-patch+json".parse().unwrap(),
+        );
+        let res = transport
+            .send(
+                &HTTPVerb::Put,
+                "http://localhost/pets/1",
+                headers,
+                &HashMap::new(),
+                None,
+            )
+            .expect("Request over a unix socket should succeed");
+        let request = handle.join().unwrap();
+        let _ = std::fs::remove_file(socket_path);
+        assert_eq!(res.status_code, 204);
+        let content_types: Vec<&str> = request
+            .lines()
+            .filter(|line| line.to_ascii_lowercase().starts_with("content-type:"))
+            .collect();
+        assert_eq!(
+            content_types,
+            vec!["content-type: application/merge-patch+json"]
+        );
+    }
+
+    #[test]
+    /// Test that connecting to a socket nobody is listening on is a connection error
+    fn unix_socket_missing() {
+        let transport = UnixSocketTransport::new(PathBuf::from("/tmp/chimerascript-no-such.sock"));
+        let res = transport.send(
+            &HTTPVerb::Get,
+            "http://localhost/",
+            HeaderMap::new(),
+            &HashMap::new(),
+            None,
+        );
+        assert_eq!(res.err(), Some(TransportError::Connect));
+    }
+
+    #[test]
+    /// Test that a server which never responds is a timeout once the timeout passes
+    fn unix_socket_timeout() {
+        let socket_path =
+            std::env::temp_dir().join(format!("chimerascript-timeout-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&socket_path);
+        let listener = UnixListener::bind(&socket_path).expect("Failed to bind a test socket");
+        let handle = thread::spawn(move || {
+            // Hold the connection open without ever answering
+            let (_stream, _) = listener.accept().expect("Failed to accept a connection");
+            thread::sleep(Duration::from_millis(500));
+        });
+        let transport = UnixSocketTransport::new(socket_path.clone());
+        let res = transport.send(
+            &HTTPVerb::Get,
+            "http://localhost/",
+            HeaderMap::new(),
+            &HashMap::new(),
+            Some(Duration::from_millis(50)),
+        );
+        handle.join().unwrap();
+        let _ = std::fs::remove_file(socket_path);
+        assert_eq!(res.err(), Some(TransportError::Timeout));
+    }
+}
@@ -55,7 +55,7 @@ impl VariableMap {
         &self,
         context: &Context,
         key: &str,
-    ) -> Result<RefMut<DataKind>, ChimeraRuntimeFailure> {
+    ) -> Result<RefMut<'_, DataKind>, ChimeraRuntimeFailure> {
         match self.map.get(key) {
             Some(var_value) => var_value.borrow_mut(),
             None => Err(ChimeraRuntimeFailure::VarNotFound(
@@ -67,4 +67,13 @@ impl VariableMap {
     pub fn insert(&mut self, key: String, value: Data) {
         self.map.insert(key, value);
     }
+    /// Copy every variable, so changes made to the copy do not change these variables
+    pub fn deep_copy(&self) -> Result<Self, ChimeraRuntimeFailure> {
+        let map = self
+            .map
+            .iter()
+            .map(|(key, value)| Ok((key.clone(), value.deep_copy()?)))
+            .collect::<Result<HashMap<String, Data>, ChimeraRuntimeFailure>>()?;
+        Ok(Self { map })
+    }
 }